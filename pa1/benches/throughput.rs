@@ -0,0 +1,39 @@
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+
+use pal::{PARSER, SCANNER};
+
+fn generated_input() -> String {
+    "total_1 + 0xFF * (rate - 3.5e1) / 7 % n ^ 2; ".repeat(20_000)
+}
+
+fn tokenize(c: &mut Criterion) {
+    let input = generated_input();
+    let count = SCANNER::constructor(input.clone()).tokenize_the_line().len();
+    let mut group = c.benchmark_group("tokenize");
+    group.throughput(Throughput::Elements(count as u64));
+    group.bench_function("get_next_token", |b| {
+        b.iter_batched(
+            || input.clone(),
+            |text| SCANNER::constructor(text).tokenize_the_line(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let tokens = SCANNER::constructor(generated_input()).tokenize_the_line();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(tokens.len() as u64));
+    group.bench_function("program", |b| {
+        b.iter_batched(
+            || tokens.clone(),
+            |tokens| PARSER::constructor(tokens).parse().unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, tokenize, parse);
+criterion_main!(benches);
@@ -1,17 +1,25 @@
 #!/bin/bash
 
 # Array of test case numbers
-tests=(1 2 3 4 5)
+tests=(1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32 33 34 35 36 37 38 39 40 41 42 43 44 45 46 47 48)
+
+# Build once up front so compiler output never ends up in a test's output
+cargo build -q
 
 # Loop through each test case
 for i in "${tests[@]}"; do
   input="test${i}.input"
   expected="test${i}.output"
+  # optional extra CLI flags for this case, e.g. --no-epsilon
+  args=()
+  if [ -f "test${i}.args" ]; then
+    read -r -a args < "test${i}.args"
+  fi
 
   echo -n "Test $i: "
 
   # Compare program output to expected output
-  if cargo run "$input" | diff -u - "$expected" > /dev/null; then
+  if cargo run -q -- "${args[@]}" "$input" 2>&1 | diff -u - "$expected" > /dev/null; then
     echo "✅ Passed"
   else
     echo "❌ Failed"
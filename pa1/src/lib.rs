@@ -0,0 +1,3114 @@
+#![allow(clippy::upper_case_acronyms)]
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::num::IntErrorKind;
+
+// ===== TOKENS & SCANNER =====
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TOKEN {
+    IDENTIFIER(String),
+    NUMBER(String),
+    STRING(String),
+    PLUS,
+    MINUS,
+    STAR,
+    SLASH,
+    PERCENT,
+    CARET,
+    POW,
+    LT,
+    GT,
+    LE,
+    GE,
+    EQ,
+    NE,
+    ASSIGN,
+    SEMICOLON,
+    COMMA,
+    AND,
+    OR,
+    BANG,
+    QUESTION,
+    COLON,
+    BOPEN,
+    BCLOSE,
+    // [ ] and { }, which group like ( ) but must close with their own kind
+    SQOPEN,
+    SQCLOSE,
+    CBOPEN,
+    CBCLOSE,
+    ERROR(char),
+    // a NUMBER too big for i64 (or f64), from a scanner with check_numbers on
+    OVERFLOW(String),
+    // a `/*` comment never closed by `*/`, positioned at its opening `/*`
+    UNCLOSEDCOMMENT,
+    // only from a scanner with keep_whitespace on: a run of whitespace other
+    // than '\n' (a '\r' included), and each '\n'
+    WHITESPACE(String),
+    NEWLINE,
+    EOF,
+}
+
+// the inverse of the escapes scan_string understands
+fn string_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl TOKEN {
+    // the variant's name, like PLUS or NUMBER, whatever the token's text
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TOKEN::IDENTIFIER(_) => "IDENTIFIER",
+            TOKEN::NUMBER(_) => "NUMBER",
+            TOKEN::STRING(_) => "STRING",
+            TOKEN::PLUS => "PLUS",
+            TOKEN::MINUS => "MINUS",
+            TOKEN::STAR => "STAR",
+            TOKEN::SLASH => "SLASH",
+            TOKEN::PERCENT => "PERCENT",
+            TOKEN::CARET => "CARET",
+            TOKEN::POW => "POW",
+            TOKEN::LT => "LT",
+            TOKEN::GT => "GT",
+            TOKEN::LE => "LE",
+            TOKEN::GE => "GE",
+            TOKEN::EQ => "EQ",
+            TOKEN::NE => "NE",
+            TOKEN::ASSIGN => "ASSIGN",
+            TOKEN::SEMICOLON => "SEMICOLON",
+            TOKEN::COMMA => "COMMA",
+            TOKEN::AND => "AND",
+            TOKEN::OR => "OR",
+            TOKEN::BANG => "BANG",
+            TOKEN::QUESTION => "QUESTION",
+            TOKEN::COLON => "COLON",
+            TOKEN::BOPEN => "BOPEN",
+            TOKEN::BCLOSE => "BCLOSE",
+            TOKEN::SQOPEN => "SQOPEN",
+            TOKEN::SQCLOSE => "SQCLOSE",
+            TOKEN::CBOPEN => "CBOPEN",
+            TOKEN::CBCLOSE => "CBCLOSE",
+            TOKEN::ERROR(_) => "ERROR",
+            TOKEN::OVERFLOW(_) => "OVERFLOW",
+            TOKEN::UNCLOSEDCOMMENT => "UNCLOSEDCOMMENT",
+            TOKEN::WHITESPACE(_) => "WHITESPACE",
+            TOKEN::NEWLINE => "NEWLINE",
+            TOKEN::EOF => "EOF",
+        }
+    }
+}
+
+impl fmt::Display for TOKEN {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TOKEN::IDENTIFIER(name) => write!(f, "{}", name),
+            TOKEN::NUMBER(n) => write!(f, "{}", n),
+            TOKEN::STRING(text) => write!(f, "\"{}\"", string_escape(text)),
+            TOKEN::PLUS => write!(f, "+"),
+            TOKEN::MINUS => write!(f, "-"),
+            TOKEN::STAR => write!(f, "*"),
+            TOKEN::SLASH => write!(f, "/"),
+            TOKEN::PERCENT => write!(f, "%"),
+            TOKEN::CARET => write!(f, "^"),
+            TOKEN::POW => write!(f, "**"),
+            TOKEN::LT => write!(f, "<"),
+            TOKEN::GT => write!(f, ">"),
+            TOKEN::LE => write!(f, "<="),
+            TOKEN::GE => write!(f, ">="),
+            TOKEN::EQ => write!(f, "=="),
+            TOKEN::NE => write!(f, "!="),
+            TOKEN::ASSIGN => write!(f, "="),
+            TOKEN::SEMICOLON => write!(f, ";"),
+            TOKEN::COMMA => write!(f, ","),
+            TOKEN::AND => write!(f, "&&"),
+            TOKEN::OR => write!(f, "||"),
+            TOKEN::BANG => write!(f, "!"),
+            TOKEN::QUESTION => write!(f, "?"),
+            TOKEN::COLON => write!(f, ":"),
+            TOKEN::BOPEN => write!(f, "("),
+            TOKEN::BCLOSE => write!(f, ")"),
+            TOKEN::SQOPEN => write!(f, "["),
+            TOKEN::SQCLOSE => write!(f, "]"),
+            TOKEN::CBOPEN => write!(f, "{{"),
+            TOKEN::CBCLOSE => write!(f, "}}"),
+            TOKEN::ERROR(c) => write!(f, "{}", c),
+            TOKEN::OVERFLOW(text) | TOKEN::WHITESPACE(text) => write!(f, "{}", text),
+            TOKEN::UNCLOSEDCOMMENT => write!(f, "/*"),
+            TOKEN::NEWLINE => writeln!(f),
+            TOKEN::EOF => write!(f, "<eof>"),
+        }
+    }
+}
+
+// where a character sits in the scanner's input. line and column are 1-based
+// and count chars, so 'é' is one column; offset is the 0-based byte offset into
+// the UTF-8 input, for slicing the original string
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct POSITION {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+// a lexing problem that tokenize() reports instead of an ERROR token
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanError {
+    InvalidCharacter { found: char, at: POSITION },
+    UnterminatedString { at: POSITION },
+    UnterminatedComment { at: POSITION },
+    NumberOutOfRange { text: String, at: POSITION },
+}
+
+impl ScanError {
+    pub fn position(&self) -> POSITION {
+        match self {
+            ScanError::InvalidCharacter { at, .. }
+            | ScanError::UnterminatedString { at }
+            | ScanError::UnterminatedComment { at }
+            | ScanError::NumberOutOfRange { at, .. } => *at,
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScanError::InvalidCharacter { found, .. } => {
+                write!(f, "invalid character '{}'", found)
+            }
+            ScanError::UnterminatedString { .. } => write!(f, "unterminated string"),
+            ScanError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+            ScanError::NumberOutOfRange { text, .. } => write!(f, "number '{}' out of range", text),
+        }
+    }
+}
+
+// what the scanner found, before any text is copied out of the input
+enum Lexeme {
+    Identifier,
+    Number,
+    Other(TOKEN),
+}
+
+// a token whose identifier or number text borrows from the scanned source, as
+// produced by tokenize_borrowed; every other token is carried as is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
+    Identifier(&'a str),
+    Number(&'a str),
+    Other(TOKEN),
+}
+
+// which operators the scanner accepts; a disabled one scans as TOKEN::ERROR,
+// so the parser and tokenize() reject it like any other bad character.
+// Everything is on by default, and each builder method turns one group on or off:
+// ScannerConfig::default().star(false) is the language without multiplication
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScannerConfig {
+    pub plus: bool,
+    pub minus: bool,
+    pub star: bool,
+    pub slash: bool,
+    pub percent: bool,
+    pub power: bool,
+    pub comparison: bool,
+    pub equality: bool,
+    pub logical: bool,
+    pub ternary: bool,
+    pub assign: bool,
+    pub parens: bool,
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        ScannerConfig {
+            plus: true,
+            minus: true,
+            star: true,
+            slash: true,
+            percent: true,
+            power: true,
+            comparison: true,
+            equality: true,
+            logical: true,
+            ternary: true,
+            assign: true,
+            parens: true,
+        }
+    }
+}
+
+impl ScannerConfig {
+    // +
+    pub fn plus(mut self, on: bool) -> Self {
+        self.plus = on;
+        self
+    }
+
+    // - (both binary and unary)
+    pub fn minus(mut self, on: bool) -> Self {
+        self.minus = on;
+        self
+    }
+
+    // *
+    pub fn star(mut self, on: bool) -> Self {
+        self.star = on;
+        self
+    }
+
+    // /
+    pub fn slash(mut self, on: bool) -> Self {
+        self.slash = on;
+        self
+    }
+
+    // %
+    pub fn percent(mut self, on: bool) -> Self {
+        self.percent = on;
+        self
+    }
+
+    // ^ and **
+    pub fn power(mut self, on: bool) -> Self {
+        self.power = on;
+        self
+    }
+
+    // <, >, <= and >=
+    pub fn comparison(mut self, on: bool) -> Self {
+        self.comparison = on;
+        self
+    }
+
+    // == and !=
+    pub fn equality(mut self, on: bool) -> Self {
+        self.equality = on;
+        self
+    }
+
+    // &&, || and !
+    pub fn logical(mut self, on: bool) -> Self {
+        self.logical = on;
+        self
+    }
+
+    // ? and :
+    pub fn ternary(mut self, on: bool) -> Self {
+        self.ternary = on;
+        self
+    }
+
+    // =
+    pub fn assign(mut self, on: bool) -> Self {
+        self.assign = on;
+        self
+    }
+
+    // ( and ), [ and ], { and }
+    pub fn parens(mut self, on: bool) -> Self {
+        self.parens = on;
+        self
+    }
+
+    // whether `tok` belongs to an enabled group; non-operators always pass
+    pub fn allows(&self, tok: &TOKEN) -> bool {
+        match tok {
+            TOKEN::PLUS => self.plus,
+            TOKEN::MINUS => self.minus,
+            TOKEN::STAR => self.star,
+            TOKEN::SLASH => self.slash,
+            TOKEN::PERCENT => self.percent,
+            TOKEN::CARET | TOKEN::POW => self.power,
+            TOKEN::LT | TOKEN::GT | TOKEN::LE | TOKEN::GE => self.comparison,
+            TOKEN::EQ | TOKEN::NE => self.equality,
+            TOKEN::AND | TOKEN::OR | TOKEN::BANG => self.logical,
+            TOKEN::QUESTION | TOKEN::COLON => self.ternary,
+            TOKEN::ASSIGN => self.assign,
+            TOKEN::BOPEN | TOKEN::BCLOSE => self.parens,
+            TOKEN::SQOPEN | TOKEN::SQCLOSE | TOKEN::CBOPEN | TOKEN::CBCLOSE => self.parens,
+            _ => true,
+        }
+    }
+}
+
+pub struct SCANNER<'a> {
+    // the input is kept as UTF-8 and walked by byte offset, so scanning a
+    // long line costs no more memory than the line itself
+    byte_index: usize,
+    user_input: Cow<'a, str>,
+    line: usize,
+    column: usize,
+    token_start: POSITION,
+    // chars consumed so far, and the count where the current token began,
+    // for tokenize_with_spans
+    chars_read: usize,
+    token_start_char: usize,
+    eof_emitted: bool,
+    config: ScannerConfig,
+    check_numbers: bool,
+    keep_whitespace: bool,
+}
+
+impl<'a> SCANNER<'a> {
+    pub fn constructor(input_string: String) -> Self {
+        SCANNER::over(Cow::Owned(input_string))
+    }
+
+    fn over(user_input: Cow<'a, str>) -> Self {
+        SCANNER {
+            byte_index: 0,
+            user_input,
+            line: 1,
+            column: 1,
+            token_start: POSITION { line: 1, column: 1, offset: 0 },
+            chars_read: 0,
+            token_start_char: 0,
+            eof_emitted: false,
+            config: ScannerConfig::default(),
+            check_numbers: false,
+            keep_whitespace: false,
+        }
+    }
+
+    // number lines from `line` instead of 1, for input that is fed line by line
+    pub fn at_line(mut self, line: usize) -> Self {
+        self.line = line;
+        self
+    }
+
+    // starts over on `input` as if freshly constructed, keeping the config.
+    // `input` is copied into the buffer the scanner already owns, so a loop
+    // that resets one scanner per line only allocates when a line is longer
+    // than any before it
+    pub fn reset(&mut self, input: &str) {
+        match &mut self.user_input {
+            Cow::Owned(buffer) => {
+                buffer.clear();
+                buffer.push_str(input);
+            }
+            borrowed => *borrowed = Cow::Owned(input.to_string()),
+        }
+        self.byte_index = 0;
+        self.line = 1;
+        self.column = 1;
+        self.token_start = POSITION { line: 1, column: 1, offset: 0 };
+        self.chars_read = 0;
+        self.token_start_char = 0;
+        self.eof_emitted = false;
+    }
+
+    // the text being scanned
+    pub fn source(&self) -> &str {
+        &self.user_input
+    }
+
+    // scan a NUMBER that doesn't fit an i64 (or, with a point or exponent, a
+    // finite f64) as OVERFLOW, so a bad literal fails where it is written
+    // instead of at eval time
+    pub fn check_numbers(mut self, on: bool) -> Self {
+        self.check_numbers = on;
+        self
+    }
+
+    // for formatters: emit the spacing as WHITESPACE and NEWLINE tokens
+    // instead of skipping it, so the tokens' text adds back up to the input
+    // minus its comments. PARSER does not expect these tokens; filter them out
+    // before parsing
+    pub fn keep_whitespace(mut self, keep: bool) -> Self {
+        self.keep_whitespace = keep;
+        self
+    }
+
+    // only accept the operators `config` enables
+    pub fn with_config(mut self, config: ScannerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn current_position(&self) -> POSITION {
+        POSITION { line: self.line, column: self.column, offset: self.byte_index }
+    }
+
+    // the byte offset in the original string of the char at `char_index`
+    pub fn byte_offset(&self, char_index: usize) -> usize {
+        let mut starts = self.user_input.char_indices().map(|(i, _)| i);
+        starts.nth(char_index).unwrap_or(self.user_input.len())
+    }
+
+    fn look_up_current_char(&self) -> Option<char> {
+        self.user_input[self.byte_index..].chars().next()
+    }
+
+    fn look_up_char_ahead(&self, offset: usize) -> Option<char> {
+        self.user_input[self.byte_index..].chars().nth(offset)
+    }
+
+    fn move_to_next_char(&mut self) -> Option<char> {
+        let c = self.look_up_current_char();
+        if let Some(ch) = c {
+            self.byte_index += ch.len_utf8();
+            self.chars_read += 1;
+            if ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else if ch == '\r' && self.look_up_current_char() == Some('\n') {
+                // the '\r' of a "\r\n" line ending is part of the break, not a column
+            } else {
+                self.column += 1;
+            }
+        }
+        c
+    }
+
+    // anything char::is_whitespace accepts, so Unicode spaces such as U+00A0
+    // (no-break space) separate tokens just like ' ', and '\r' never reaches a
+    // token; stops at end of input
+    fn skip_whitespace(&mut self) {
+        while matches!(self.look_up_current_char(), Some(ch) if ch.is_whitespace()) {
+            self.move_to_next_char();
+        }
+    }
+
+    // whitespace (unless keep_whitespace is on), `// ...` comments (to the end
+    // of the line) and `/* ... */` comments, which may span lines but do not
+    // nest. A single '/' is left alone so it still scans as SLASH. An
+    // unterminated block comment comes back as UNCLOSEDCOMMENT.
+    fn skip_whitespace_and_comments(&mut self) -> Option<TOKEN> {
+        loop {
+            if !self.keep_whitespace {
+                self.skip_whitespace();
+            }
+            match (self.look_up_current_char(), self.look_up_char_ahead(1)) {
+                (Some('/'), Some('/')) => {
+                    while matches!(self.look_up_current_char(), Some(ch) if ch != '\n') {
+                        self.move_to_next_char();
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    let (opened_at, opened_char) = (self.current_position(), self.chars_read);
+                    self.move_to_next_char();
+                    self.move_to_next_char();
+                    loop {
+                        match self.move_to_next_char() {
+                            Some('*') if self.look_up_current_char() == Some('/') => {
+                                self.move_to_next_char();
+                                break;
+                            }
+                            Some(_) => {}
+                            None => {
+                                self.token_start = opened_at;
+                                self.token_start_char = opened_char;
+                                return Some(TOKEN::UNCLOSEDCOMMENT);
+                            }
+                        }
+                    }
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    // small helper to consume chars while a predicate holds
+    fn skip_while<F: Fn(char) -> bool>(&mut self, keep: F) {
+        while matches!(self.look_up_current_char(), Some(c) if keep(c)) {
+            self.move_to_next_char();
+        }
+    }
+
+    // the source text of the token being scanned, up to the current char
+    fn lexeme_text(&self) -> &str {
+        &self.user_input[self.token_start.offset..self.byte_index]
+    }
+
+    // a run of digits that may use single underscores as separators (1_000),
+    // starting at byte `from` with its first char already consumed; false when
+    // an underscore is doubled, trailing, or right after the point
+    fn digits_ok(&mut self, from: usize) -> bool {
+        self.skip_while(|c| c.is_ascii_digit() || c == '_');
+        let run = &self.user_input[from..self.byte_index];
+        !(run.contains("__") || run.ends_with('_') || run.starts_with("._"))
+    }
+
+    // a malformed number literal comes back as ERROR(bad), where `bad` is the
+    // char that broke it. The rest of its run of digits, letters, '_' and '.'
+    // is consumed too, so `1.e3` is one error rather than an error and `e3`.
+    // A literal is malformed by a bad '.' or '_', or a prefix with no digits;
+    // a letter or digit that merely cannot continue a well-formed literal
+    // starts the next token instead (`1x`, `5e`, `0b102`)
+    fn malformed_number(&mut self, bad: char) -> Lexeme {
+        self.skip_while(|c| c.is_alphanumeric() || c == '_' || c == '.');
+        Lexeme::Other(TOKEN::ERROR(bad))
+    }
+
+    // NUMBER -> ( [0-9]+ ( . [0-9]+ )?  |  . [0-9]+ ) ( (e|E) (+|-)? [0-9]+ )?
+    // `first` is the already-consumed digit or leading '.'. A fraction needs at
+    // least one digit after the point (`1.` and `1.e3` are errors), and a
+    // second point makes the whole lexeme an ERROR instead of splitting it
+    // into two numbers. An `e` that is not followed by digits is left alone
+    // and scans as an identifier, like any other letter after a number.
+    // Underscore separators are kept in the NUMBER text as written.
+    fn scan_number(&mut self, first: char) -> Lexeme {
+        if !self.digits_ok(self.token_start.offset) {
+            return self.malformed_number('_');
+        }
+
+        if first != '.' && self.look_up_current_char() == Some('.') {
+            let point = self.byte_index;
+            self.move_to_next_char();
+            if !self.digits_ok(point) {
+                return self.malformed_number('_');
+            }
+            if self.byte_index == point + 1 {
+                return self.malformed_number('.');
+            }
+        }
+
+        if matches!(self.look_up_current_char(), Some('e' | 'E')) {
+            let sign = matches!(self.look_up_char_ahead(1), Some('+' | '-'));
+            let digit_at = if sign { 2 } else { 1 };
+            if matches!(self.look_up_char_ahead(digit_at), Some(c) if c.is_ascii_digit()) {
+                for _ in 0..digit_at {
+                    self.move_to_next_char(); // 'e' and the sign
+                }
+                let digits = self.byte_index;
+                self.move_to_next_char();
+                if !self.digits_ok(digits) {
+                    return self.malformed_number('_');
+                }
+            }
+        }
+
+        if self.look_up_current_char() == Some('.') {
+            return self.malformed_number('.');
+        }
+        Lexeme::Number
+    }
+
+    // NUMBER -> 0 (x|X) [0-9a-fA-F]+  |  0 (b|B) [01]+
+    // called with the scanner on the prefix letter; an empty body is an ERROR.
+    // So is a '_' or '.' right after the body, as in `0xF_F` or the hex float
+    // `0x1.8`, which is not supported. Any other digit or letter just ends the
+    // literal: `0b102` is 0b10 then 2, and `0xFG` is 0xF then G
+    fn scan_prefixed(&mut self, keep: fn(char) -> bool) -> Lexeme {
+        let prefix = self.move_to_next_char().unwrap();
+        match self.look_up_current_char() {
+            Some(c) if keep(c) => {
+                self.skip_while(keep);
+                match self.look_up_current_char() {
+                    Some(bad @ ('_' | '.')) => self.malformed_number(bad),
+                    _ => Lexeme::Number,
+                }
+            }
+            _ => self.malformed_number(prefix),
+        }
+    }
+
+    // STRING -> " (char | \" | \n | \\)* "   the opening quote is already consumed.
+    // Any other backslash is kept as is. A missing closing quote gives ERROR('"').
+    fn scan_string(&mut self) -> TOKEN {
+        let mut text = String::new();
+        loop {
+            match self.move_to_next_char() {
+                None => return TOKEN::ERROR('"'),
+                Some('"') => return TOKEN::STRING(text),
+                Some('\\') => match self.look_up_current_char() {
+                    Some(escaped @ ('"' | '\\')) => {
+                        self.move_to_next_char();
+                        text.push(escaped);
+                    }
+                    Some('n') => {
+                        self.move_to_next_char();
+                        text.push('\n');
+                    }
+                    _ => text.push('\\'),
+                },
+                Some(ch) => text.push(ch),
+            }
+        }
+    }
+
+    // the next lexeme, with token_start set to where it begins
+    fn next_lexeme(&mut self) -> Option<Lexeme> {
+        if let Some(unterminated) = self.skip_whitespace_and_comments() {
+            return Some(Lexeme::Other(unterminated));
+        }
+        self.token_start = self.current_position();
+        self.token_start_char = self.chars_read;
+        if self.keep_whitespace {
+            match self.look_up_current_char() {
+                Some('\n') => {
+                    self.move_to_next_char();
+                    return Some(Lexeme::Other(TOKEN::NEWLINE));
+                }
+                Some(c) if c.is_whitespace() => {
+                    self.skip_while(|c| c.is_whitespace() && c != '\n');
+                    return Some(Lexeme::Other(TOKEN::WHITESPACE(self.lexeme_text().to_string())));
+                }
+                _ => {}
+            }
+        }
+        let ch = self.move_to_next_char()?;
+
+        Some(match ch {
+            '0' if matches!(self.look_up_current_char(), Some('x' | 'X')) => {
+                self.scan_prefixed(|c| c.is_ascii_hexdigit())
+            }
+            '0' if matches!(self.look_up_current_char(), Some('b' | 'B')) => {
+                self.scan_prefixed(|c| c == '0' || c == '1')
+            }
+            d if d.is_ascii_digit() => self.scan_number(d),
+            '.' if matches!(self.look_up_current_char(), Some(c) if c.is_ascii_digit()) => {
+                self.scan_number('.')
+            }
+            // a leading digit separator, as in `_5`, is a malformed number
+            '_' if matches!(self.look_up_current_char(), Some(c) if c.is_ascii_digit()) => {
+                self.malformed_number('_')
+            }
+            // IDENTIFIER -> (letter | _) (letter | digit | _)*
+            a if a.is_alphabetic() || a == '_' => {
+                self.skip_while(|c| c.is_alphabetic() || c.is_ascii_digit() || c == '_');
+                Lexeme::Identifier
+            }
+            other => Lexeme::Other(self.scan_symbol(other)),
+        })
+    }
+
+    // operators, punctuation and strings; `ch` is already consumed
+    fn scan_symbol(&mut self, ch: char) -> TOKEN {
+        // a disabled operator is still consumed whole, so `**` is one error
+        let tok = self.scan_operator(ch);
+        if self.config.allows(&tok) { tok } else { TOKEN::ERROR(ch) }
+    }
+
+    fn scan_operator(&mut self, ch: char) -> TOKEN {
+        match ch {
+            '+' => TOKEN::PLUS,
+            '-' => TOKEN::MINUS,
+            // only an adjacent second '*' makes **, so `2 * * 3` is two STARs
+            '*' if self.look_up_current_char() == Some('*') => {
+                self.move_to_next_char(); // second '*'
+                TOKEN::POW
+            }
+            '*' => TOKEN::STAR,
+            '/' => TOKEN::SLASH,
+            '%' => TOKEN::PERCENT,
+            '^' => TOKEN::CARET,
+            '<' | '>' if self.look_up_current_char() == Some('=') => {
+                self.move_to_next_char(); // '='
+                if ch == '<' { TOKEN::LE } else { TOKEN::GE }
+            }
+            '<' => TOKEN::LT,
+            '>' => TOKEN::GT,
+            '=' | '!' if self.look_up_current_char() == Some('=') => {
+                self.move_to_next_char(); // '='
+                if ch == '=' { TOKEN::EQ } else { TOKEN::NE }
+            }
+            '=' => TOKEN::ASSIGN,
+            '!' => TOKEN::BANG,
+            '?' => TOKEN::QUESTION,
+            ':' => TOKEN::COLON,
+            ';' => TOKEN::SEMICOLON,
+            ',' => TOKEN::COMMA,
+            // a lone '&' or '|' is an error for now
+            '&' if self.look_up_current_char() == Some('&') => {
+                self.move_to_next_char(); // second '&'
+                TOKEN::AND
+            }
+            '|' if self.look_up_current_char() == Some('|') => {
+                self.move_to_next_char(); // second '|'
+                TOKEN::OR
+            }
+            '"' => self.scan_string(),
+            '(' => TOKEN::BOPEN,
+            ')' => TOKEN::BCLOSE,
+            '[' => TOKEN::SQOPEN,
+            ']' => TOKEN::SQCLOSE,
+            '{' => TOKEN::CBOPEN,
+            '}' => TOKEN::CBCLOSE,
+            other => TOKEN::ERROR(other),
+        }
+    }
+
+    pub fn get_next_token(&mut self) -> Option<TOKEN> {
+        Some(match self.next_lexeme()? {
+            Lexeme::Identifier => TOKEN::IDENTIFIER(self.lexeme_text().to_string()),
+            Lexeme::Number if self.check_numbers && !number_fits(self.lexeme_text()) => {
+                TOKEN::OVERFLOW(self.lexeme_text().to_string())
+            }
+            Lexeme::Number => TOKEN::NUMBER(self.lexeme_text().to_string()),
+            Lexeme::Other(tok) => tok,
+        })
+    }
+
+    // tokens plus the position each one starts at; EOF sits just past the input
+    pub fn tokenize_with_positions(&mut self) -> (Vec<TOKEN>, Vec<POSITION>) {
+        let (tokens, positions, _) = self.tokenize_with_spans();
+        (tokens, positions)
+    }
+
+    // like tokenize_with_positions, plus the (start, end) char offsets each
+    // token covers, end exclusive; EOF gets an empty span at the end
+    pub fn tokenize_with_spans(&mut self) -> (Vec<TOKEN>, Vec<POSITION>, Vec<(usize, usize)>) {
+        let mut tokens = Vec::new();
+        let mut positions = Vec::new();
+        let mut spans = Vec::new();
+        while let Some(tok) = self.get_next_token() {
+            tokens.push(tok);
+            positions.push(self.token_start);
+            spans.push((self.token_start_char, self.chars_read));
+        }
+        tokens.push(TOKEN::EOF);
+        positions.push(self.current_position());
+        spans.push((self.chars_read, self.chars_read));
+        self.eof_emitted = true;
+        (tokens, positions, spans)
+    }
+
+    pub fn tokenize_the_line(&mut self) -> Vec<TOKEN> {
+        self.collect()
+    }
+}
+
+// yields each token, then a single EOF, then None
+impl Iterator for SCANNER<'_> {
+    type Item = TOKEN;
+
+    fn next(&mut self) -> Option<TOKEN> {
+        if let Some(tok) = self.get_next_token() {
+            return Some(tok);
+        }
+        if self.eof_emitted {
+            None
+        } else {
+            self.eof_emitted = true;
+            Some(TOKEN::EOF)
+        }
+    }
+}
+
+// ===== Minimal tree to control printed layout =====
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NODE {
+    pub label: String,
+    pub children: Vec<NODE>,
+    // (start, end) char offsets of the source this node covers, end exclusive.
+    // None when the parser had no spans, and for EPSILON and ERROR leaves
+    pub span: Option<(usize, usize)>,
+    // this node's number in a pre-order walk of its tree, for referring to it
+    // from outside. 0 until assign_ids runs; PARSER::parse, prune_epsilon and
+    // ast_to_node number the trees they return. The DOT and JSON exports
+    // number nodes the same way as they write them, so they match these ids
+    // and stay unique even on a hand-built tree
+    pub id: usize,
+}
+
+impl NODE {
+    pub fn leaf(label: &str) -> NODE {
+        NODE { label: label.to_string(), children: Vec::new(), span: None, id: 0 }
+    }
+    // the span is the union of the children's
+    pub fn with(label: &str, children: Vec<NODE>) -> NODE {
+        let starts = children.iter().filter_map(|c| c.span).map(|(start, _)| start);
+        let ends = children.iter().filter_map(|c| c.span).map(|(_, end)| end);
+        let span = starts.min().zip(ends.max());
+        NODE { label: label.to_string(), children, span, id: 0 }
+    }
+    pub fn at(mut self, span: Option<(usize, usize)>) -> NODE {
+        self.span = span;
+        self
+    }
+}
+
+// numbers `root` and everything under it 0, 1, 2, ... in pre-order (a node
+// before its children, children left to right) and returns how many nodes
+// there are. Walks an explicit stack, like Drop below
+pub fn assign_ids(root: &mut NODE) -> usize {
+    let mut next = 0;
+    let mut pending = vec![root];
+    while let Some(node) = pending.pop() {
+        node.id = next;
+        next += 1;
+        pending.extend(node.children.iter_mut().rev());
+    }
+    next
+}
+
+// drops the tree from an explicit list of nodes rather than recursively, so a
+// very deep tree (see PARSER::iterative) cannot overflow the stack on the way out
+impl Drop for NODE {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.children);
+        while let Some(mut node) = pending.pop() {
+            pending.append(&mut node.children);
+        }
+    }
+}
+
+// trees are equal when their labels and children are; spans and ids are
+// ignored, so a parsed tree can be compared with one built by hand
+impl PartialEq for NODE {
+    fn eq(&self, other: &NODE) -> bool {
+        self.label == other.label && self.children == other.children
+    }
+}
+
+// ===== PARSER =====
+
+#[derive(Debug)]
+pub enum ParseError {
+    MissingClosingParen { opened_at: Option<POSITION>, at: Option<POSITION> },
+    UnclosedBracket { open: char, opened_at: Option<POSITION>, at: Option<POSITION> },
+    MismatchedBracket {
+        open: char,
+        found: char,
+        opened_at: Option<POSITION>,
+        at: Option<POSITION>,
+    },
+    InvalidCharacter { found: char, at: Option<POSITION> },
+    UnterminatedComment { at: Option<POSITION> },
+    UnexpectedToken { found: String, at: Option<POSITION> },
+    TrailingToken { found: String, at: Option<POSITION> },
+    MissingColon { question_at: Option<POSITION>, at: Option<POSITION> },
+    TooDeep { limit: usize, at: Option<POSITION> },
+    NumberOutOfRange { text: String, at: Option<POSITION> },
+}
+
+impl ParseError {
+    pub fn position(&self) -> Option<POSITION> {
+        match self {
+            ParseError::MissingClosingParen { at, .. }
+            | ParseError::UnclosedBracket { at, .. }
+            | ParseError::MismatchedBracket { at, .. }
+            | ParseError::InvalidCharacter { at, .. }
+            | ParseError::UnterminatedComment { at }
+            | ParseError::UnexpectedToken { at, .. }
+            | ParseError::TrailingToken { at, .. }
+            | ParseError::MissingColon { at, .. }
+            | ParseError::TooDeep { at, .. }
+            | ParseError::NumberOutOfRange { at, .. } => *at,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::MissingClosingParen { opened_at: Some(p), .. } => {
+                write!(f, "unclosed '(' opened at line {}, column {}", p.line, p.column)
+            }
+            ParseError::MissingClosingParen { opened_at: None, .. } => {
+                write!(f, "missing closing parenthesis")
+            }
+            ParseError::UnclosedBracket { open, opened_at: Some(p), .. } => {
+                write!(f, "unclosed '{}' opened at line {}, column {}", open, p.line, p.column)
+            }
+            ParseError::UnclosedBracket { open, opened_at: None, .. } => {
+                write!(f, "missing closing bracket for '{}'", open)
+            }
+            ParseError::MismatchedBracket { open, found, opened_at: Some(p), .. } => write!(
+                f,
+                "'{}' opened at line {}, column {} is closed by '{}'",
+                open, p.line, p.column, found
+            ),
+            ParseError::MismatchedBracket { open, found, opened_at: None, .. } => {
+                write!(f, "'{}' is closed by '{}'", open, found)
+            }
+            ParseError::InvalidCharacter { found, .. } => {
+                write!(f, "invalid character '{}'", found)
+            }
+            ParseError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+            ParseError::UnexpectedToken { found, .. } => {
+                write!(f, "expected a factor, found '{}'", found)
+            }
+            ParseError::TrailingToken { found, .. } => {
+                write!(f, "unexpected trailing token '{}'", found)
+            }
+            ParseError::MissingColon { question_at: Some(p), .. } => {
+                write!(f, "missing ':' for the '?' at line {}, column {}", p.line, p.column)
+            }
+            ParseError::MissingColon { question_at: None, .. } => {
+                write!(f, "missing ':' after '?'")
+            }
+            ParseError::TooDeep { limit, .. } => {
+                write!(f, "expression nested more than {} levels deep", limit)
+            }
+            ParseError::NumberOutOfRange { text, .. } => {
+                write!(f, "number '{}' out of range", text)
+            }
+        }
+    }
+}
+
+// the bracket closing `open`, for any of ( [ {
+fn closer_for(open: &TOKEN) -> TOKEN {
+    match open {
+        TOKEN::SQOPEN => TOKEN::SQCLOSE,
+        TOKEN::CBOPEN => TOKEN::CBCLOSE,
+        _ => TOKEN::BCLOSE,
+    }
+}
+
+// a one-character token such as a bracket, as that character
+fn token_char(token: &TOKEN) -> char {
+    token.to_string().chars().next().unwrap_or('?')
+}
+
+// the EXPRDASH or TERMDASH chain for `links` of (operator, operand), nested
+// from the right and ended by an ε link: label [op, operand, label [...]]
+fn nest_links(label: &str, links: Vec<(NODE, NODE)>) -> NODE {
+    let mut chain = NODE::with(label, vec![NODE::leaf("EPSILON")]);
+    for (op, operand) in links.into_iter().rev() {
+        chain = NODE::with(label, vec![op, operand, chain]);
+    }
+    chain
+}
+
+// the error for a token found where a factor should start
+fn not_a_factor(found: &TOKEN, at: Option<POSITION>) -> ParseError {
+    match found {
+        TOKEN::ERROR(c) => ParseError::InvalidCharacter { found: *c, at },
+        TOKEN::UNCLOSEDCOMMENT => ParseError::UnterminatedComment { at },
+        TOKEN::OVERFLOW(text) => ParseError::NumberOutOfRange { text: text.clone(), at },
+        other => ParseError::UnexpectedToken { found: other.to_string(), at },
+    }
+}
+
+pub struct PARSER {
+    index: usize,
+    tokens: Vec<TOKEN>,
+    positions: Vec<POSITION>,
+    spans: Vec<(usize, usize)>,
+    // set by parse_recovering: errors are collected here instead of returned
+    recovering: bool,
+    errors: Vec<ParseError>,
+    // when false, parse stops after a complete program and leaves the rest
+    strict: bool,
+    // how many nested() calls are active, and how many may be
+    depth: usize,
+    max_depth: usize,
+    // parse runs of '(' with parse_parens_iteratively; pending_factor is the
+    // FACTOR it hands to the next parse_factor call
+    iterative: bool,
+    pending_factor: Option<NODE>,
+    // build + and - chains right-associatively; see right_assoc_additive
+    right_assoc_additive: bool,
+    // parse_ast goes through parse_pratt instead of the recursive grammar
+    pratt: bool,
+}
+
+// the default for PARSER::max_depth. A level of parentheses takes ~20 KB of
+// stack in a debug build (~4 KB optimized), so 64 of them fit the 2 MB that
+// spawned and test threads get, with room to spare
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+impl PARSER {
+    pub fn constructor(tokens: Vec<TOKEN>) -> Self {
+        PARSER {
+            index: 0,
+            tokens,
+            positions: Vec::new(),
+            spans: Vec::new(),
+            recovering: false,
+            errors: Vec::new(),
+            strict: true,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            iterative: false,
+            right_assoc_additive: false,
+            pratt: false,
+            pending_factor: None,
+        }
+    }
+
+    // parse nested parentheses with an explicit stack instead of recursion, so
+    // ((( ... ))) of any depth fits in the call stack and does not count
+    // toward max_depth; the trees are the same either way. Such a tree can be
+    // dropped, numbered, pruned, measured (count_nodes, tree_depth) and printed
+    // in every Format, all without recursion; to_ast, eval, to_infix, walk and
+    // print_postorder still recurse and need a stack as deep as the tree
+    pub fn iterative(mut self, iterative: bool) -> Self {
+        self.iterative = iterative;
+        self
+    }
+
+    // each '(', unary operator, right-hand '^', ternary branch and assignment
+    // nests one level (a flat `+` or `*` chain does not); past `max_depth` levels parsing
+    // fails with TooDeep rather than overflowing the stack. Raising it needs
+    // ~20 KB of stack per level in a debug build, e.g. 256 levels want 5 MB
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // a grammar variant for comparing associativity: EXPRDASH becomes
+    // (+|-) EXPR | ε, recursing like POWERDASH does, so 1 - 2 - 3 groups as
+    // 1 - (2 - 3). eval and to_ast follow whichever shape they are given
+    pub fn right_assoc_additive(mut self, right: bool) -> Self {
+        self.right_assoc_additive = right;
+        self
+    }
+
+    // pick the backend parse_ast uses: parse_pratt when true, otherwise the
+    // recursive grammar followed by to_ast
+    pub fn pratt(mut self, pratt: bool) -> Self {
+        self.pratt = pratt;
+        self
+    }
+
+    // strict (the default) rejects anything after the program, ERROR tokens
+    // included; lenient parsing accepts the longest valid prefix
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    // attach token positions so errors can say where they happened
+    pub fn with_positions(mut self, positions: Vec<POSITION>) -> Self {
+        self.positions = positions;
+        self
+    }
+
+    // attach token spans, as from tokenize_with_spans, so nodes carry them
+    pub fn with_spans(mut self, spans: Vec<(usize, usize)>) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    // a leaf for the current token, spanning it if spans were attached
+    fn token_leaf(&self, label: &str) -> NODE {
+        NODE::leaf(label).at(self.spans.get(self.index).copied())
+    }
+
+    // position of the current token, if positions were attached
+    fn current_position(&self) -> Option<POSITION> {
+        self.positions.get(self.index).copied()
+    }
+
+    pub fn current_token(&self) -> &TOKEN {
+        self.tokens.get(self.index).unwrap_or(&TOKEN::EOF)
+    }
+
+    // one token of lookahead past current_token, without advancing
+    pub fn peek_token(&self) -> &TOKEN {
+        self.tokens.get(self.index + 1).unwrap_or(&TOKEN::EOF)
+    }
+
+    fn move_to_next_token(&mut self) {
+        if self.index < self.tokens.len() {
+            self.index += 1;
+        }
+    }
+
+    // entry point: parse one full expression that must use up every token
+    pub fn parse(&mut self) -> Result<NODE, ParseError> {
+        let mut tree = self.parse_program()?;
+        if self.strict {
+            self.expect_end()?;
+        }
+        assign_ids(&mut tree);
+        Ok(tree)
+    }
+
+    // what strict mode would have rejected at the point parsing stopped, if anything
+    pub fn leftover(&self) -> Option<ParseError> {
+        self.expect_end().err()
+    }
+
+    // the tokens not consumed yet, from `index` on; the closing EOF is never
+    // consumed, so once the whole input was used this is just [EOF]
+    pub fn remaining(&self) -> &[TOKEN] {
+        &self.tokens[self.index..]
+    }
+
+    // the Ast from whichever backend .pratt() picked
+    pub fn parse_ast(&mut self) -> Result<Ast, ParseError> {
+        if self.pratt {
+            return self.parse_pratt();
+        }
+        // parse never leaves ERROR leaves, so its tree always converts
+        self.parse().map(|tree| to_ast(&tree).expect("PARSER::parse output converts to an Ast"))
+    }
+
+    // a second backend that builds the Ast directly by precedence climbing,
+    // with none of the EXPRDASH/TERMDASH scaffolding. It covers numbers,
+    // identifiers, parentheses, unary minus and + - * / % ^ (or **), grouped
+    // exactly as the recursive grammar groups them; any other token ends the
+    // expression, so strict parsing reports it as trailing
+    pub fn parse_pratt(&mut self) -> Result<Ast, ParseError> {
+        let ast = self.pratt_binary(1)?;
+        if self.strict {
+            self.expect_end()?;
+        }
+        Ok(ast)
+    }
+
+    // operands joined by binary operators of level `min` or above, where + and
+    // - are level 1 and * / % level 2. Both levels are left-associative, so a
+    // right operand only takes operators that bind tighter than its own
+    fn pratt_binary(&mut self, min: u8) -> Result<Ast, ParseError> {
+        let mut lhs = self.pratt_unary()?;
+        loop {
+            let level = match self.current_token() {
+                TOKEN::PLUS | TOKEN::MINUS => 1,
+                TOKEN::STAR | TOKEN::SLASH | TOKEN::PERCENT => 2,
+                _ => return Ok(lhs),
+            };
+            if level < min {
+                return Ok(lhs);
+            }
+            // the same PLUS/STAR/... leaf the recursive parser would build
+            let op = NODE::leaf(self.current_token().kind());
+            self.move_to_next_token();
+            let rhs = self.nested(|parser| parser.pratt_binary(level + 1))?;
+            lhs = chain_link(&op, lhs, rhs).expect("op is one of + - * / %");
+        }
+    }
+
+    // as in UNARY, '-' binds tighter than * but looser than ^
+    fn pratt_unary(&mut self) -> Result<Ast, ParseError> {
+        if let TOKEN::MINUS = self.current_token() {
+            self.move_to_next_token();
+            let operand = self.nested(Self::pratt_unary)?;
+            return Ok(Ast::Neg(Box::new(operand)));
+        }
+        self.pratt_power()
+    }
+
+    // ^ is right-associative and, as in POWERDASH, its exponent can't start with '-'
+    fn pratt_power(&mut self) -> Result<Ast, ParseError> {
+        let base = self.pratt_primary()?;
+        if let TOKEN::CARET | TOKEN::POW = self.current_token() {
+            self.move_to_next_token();
+            let exponent = self.nested(Self::pratt_power)?;
+            return Ok(Ast::Pow(Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn pratt_primary(&mut self) -> Result<Ast, ParseError> {
+        let at = self.current_position();
+        let ast = match self.current_token() {
+            TOKEN::NUMBER(n) => match number_value(n) {
+                Ok(value) => Ast::Num(value),
+                Err(_) => return Err(ParseError::NumberOutOfRange { text: n.clone(), at }),
+            },
+            TOKEN::IDENTIFIER(name) => Ast::Var(name.clone()),
+            opener @ (TOKEN::BOPEN | TOKEN::SQOPEN | TOKEN::CBOPEN) => {
+                let opener = opener.clone();
+                self.move_to_next_token();
+                let inside = self.nested(|parser| parser.pratt_binary(1))?;
+                self.close_group(&opener, at)?;
+                return Ok(inside);
+            }
+            bad => return Err(not_a_factor(bad, at)),
+        };
+        self.move_to_next_token();
+        Ok(ast)
+    }
+
+    // like parse, but keeps going past errors: a bad factor becomes an ERROR
+    // leaf and the parser skips ahead to the next '+', closing bracket or EOF;
+    // a missing closer or ':' gets an ERROR leaf in its place. Returns the best-effort tree
+    // with every error found, in order.
+    pub fn parse_recovering(&mut self) -> (NODE, Vec<ParseError>) {
+        self.recovering = true;
+        let mut tree = match self.parse_program() {
+            Ok(tree) => tree,
+            // only TooDeep gets here; the tokens it stopped in are not
+            // reported again as trailing
+            Err(error) => {
+                self.errors.push(error);
+                return (NODE::leaf("ERROR"), std::mem::take(&mut self.errors));
+            }
+        };
+        if let Err(error) = self.expect_end() {
+            self.errors.push(error);
+        }
+        assign_ids(&mut tree);
+        (tree, std::mem::take(&mut self.errors))
+    }
+
+    fn expect_end(&self) -> Result<(), ParseError> {
+        let at = self.current_position();
+        match self.current_token() {
+            TOKEN::EOF => Ok(()),
+            // a bad character ends the expression early; report the character
+            // itself rather than calling it a trailing token
+            TOKEN::ERROR(c) => Err(ParseError::InvalidCharacter { found: *c, at }),
+            TOKEN::UNCLOSEDCOMMENT => Err(ParseError::UnterminatedComment { at }),
+            TOKEN::OVERFLOW(text) => Err(ParseError::NumberOutOfRange { text: text.clone(), at }),
+            other => Err(ParseError::TrailingToken {
+                found: other.to_string(),
+                at,
+            }),
+        }
+    }
+
+    // runs `parse` one level deeper. TooDeep is returned directly, not through
+    // recover, since carrying on would only hit the limit again
+    fn nested<T, F>(&mut self, parse: F) -> Result<T, ParseError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, ParseError>,
+    {
+        if self.depth >= self.max_depth {
+            let at = self.current_position();
+            return Err(ParseError::TooDeep { limit: self.max_depth, at });
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // when recovering, records `error` and hands back an ERROR leaf to stand in
+    // for what was missing; otherwise just fails with it
+    fn recover(&mut self, error: ParseError) -> Result<NODE, ParseError> {
+        if !self.recovering {
+            return Err(error);
+        }
+        self.errors.push(error);
+        Ok(NODE::leaf("ERROR"))
+    }
+
+    // skips to the next '+', closing bracket or EOF, leaving it unconsumed
+    fn synchronize(&mut self) {
+        while !matches!(
+            self.current_token(),
+            TOKEN::PLUS | TOKEN::BCLOSE | TOKEN::SQCLOSE | TOKEN::CBCLOSE | TOKEN::EOF
+        ) {
+            self.move_to_next_token();
+        }
+    }
+
+    // PROGRAM -> EXPRLIST (; EXPRLIST)* ;? | EXPRLIST
+    // a line without ';' returns its EXPRLIST as is; with one, every
+    // statement becomes a child of PROGRAM and a final ';' is allowed
+    fn parse_program(&mut self) -> Result<NODE, ParseError> {
+        let first = self.parse_expr_list()?;
+        if !matches!(self.current_token(), TOKEN::SEMICOLON) {
+            return Ok(first);
+        }
+        let mut statements = vec![first];
+        while let TOKEN::SEMICOLON = self.current_token() {
+            self.move_to_next_token(); // ';'
+            if let TOKEN::EOF = self.current_token() {
+                break;
+            }
+            statements.push(self.parse_expr_list()?);
+        }
+        Ok(NODE::with("PROGRAM", statements))
+    }
+
+    // EXPRLIST -> STATEMENT (, STATEMENT)*
+    // only at the top of a statement: inside parentheses or call arguments a
+    // ',' means something else. Unlike CALL, no COMMA leaves are kept, and a
+    // trailing ',' is an error; a lone STATEMENT is returned as is
+    fn parse_expr_list(&mut self) -> Result<NODE, ParseError> {
+        let first = self.parse_statement()?;
+        if !matches!(self.current_token(), TOKEN::COMMA) {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while let TOKEN::COMMA = self.current_token() {
+            self.move_to_next_token(); // ','
+            items.push(self.parse_statement()?);
+        }
+        Ok(NODE::with("EXPRLIST", items))
+    }
+
+    // STATEMENT -> IDENTIFIER = STATEMENT | TERNARY
+    // recursing on STATEMENT makes = right-associative, so a = b = 1 assigns b first
+    fn parse_statement(&mut self) -> Result<NODE, ParseError> {
+        let next = (self.current_token(), self.peek_token());
+        if let (TOKEN::IDENTIFIER(name), TOKEN::ASSIGN) = next {
+            let target = self.token_leaf(&format!("IDENTIFIER({})", name));
+            self.move_to_next_token(); // the identifier
+            self.move_to_next_token(); // '='
+            let value = self.nested(Self::parse_statement)?;
+            return Ok(NODE::with("ASSIGN", vec![target, value]));
+        }
+        self.parse_ternary()
+    }
+
+    // TERNARY -> OR ? TERNARY : TERNARY | OR
+    // the branches recurse on TERNARY, so a ? b : c ? d : e nests on the right;
+    // without a '?' the OR node is returned as is
+    fn parse_ternary(&mut self) -> Result<NODE, ParseError> {
+        let condition = self.parse_or()?;
+        if !matches!(self.current_token(), TOKEN::QUESTION) {
+            return Ok(condition);
+        }
+        let question_at = self.current_position();
+        self.move_to_next_token(); // '?'
+        let then = self.nested(Self::parse_ternary)?;
+        if !matches!(self.current_token(), TOKEN::COLON) {
+            let at = self.current_position();
+            let otherwise = self.recover(ParseError::MissingColon { question_at, at })?;
+            return Ok(NODE::with("TERNARY", vec![condition, then, otherwise]));
+        }
+        self.move_to_next_token(); // ':'
+        let otherwise = self.nested(Self::parse_ternary)?;
+        Ok(NODE::with("TERNARY", vec![condition, then, otherwise]))
+    }
+
+    // OR -> AND (|| AND)*
+    // left-associative, so a || b || c nests as (a || b) || c; without an
+    // operator the AND node is returned as is
+    fn parse_or(&mut self) -> Result<NODE, ParseError> {
+        let mut lhs = self.parse_and()?;
+        while let TOKEN::OR = self.current_token() {
+            let op = self.token_leaf("OR");
+            self.move_to_next_token(); // '||'
+            let rhs = self.parse_and()?;
+            lhs = NODE::with("DISJUNCTION", vec![lhs, op, rhs]);
+        }
+        Ok(lhs)
+    }
+
+    // AND -> COMPARISON (&& COMPARISON)*
+    // binds tighter than ||, and like it returns a lone COMPARISON as is
+    fn parse_and(&mut self) -> Result<NODE, ParseError> {
+        let mut lhs = self.parse_comparison()?;
+        while let TOKEN::AND = self.current_token() {
+            let op = self.token_leaf("AND");
+            self.move_to_next_token(); // '&&'
+            let rhs = self.parse_comparison()?;
+            lhs = NODE::with("CONJUNCTION", vec![lhs, op, rhs]);
+        }
+        Ok(lhs)
+    }
+
+    // COMPARISON -> EXPR (<|>|<=|>=|==|!=) EXPR | EXPR
+    // comparisons don't chain: 1 < 2 < 3 stops at the second '<', which parse()
+    // then reports; without an operator the EXPR node is returned as is
+    fn parse_comparison(&mut self) -> Result<NODE, ParseError> {
+        let lhs = self.parse_expr()?;
+        let op = match self.current_token() {
+            TOKEN::LT => "LT",
+            TOKEN::GT => "GT",
+            TOKEN::LE => "LE",
+            TOKEN::GE => "GE",
+            TOKEN::EQ => "EQ",
+            TOKEN::NE => "NE",
+            _ => return Ok(lhs),
+        };
+        let op = self.token_leaf(op);
+        self.move_to_next_token(); // the operator
+        let rhs = self.parse_expr()?;
+        Ok(NODE::with("COMPARISON", vec![lhs, op, rhs]))
+    }
+
+    // EXPR -> TERM EXPRDASH
+    fn parse_expr(&mut self) -> Result<NODE, ParseError> {
+        let t = self.parse_term()?;
+        let d = self.parse_exprdash()?;
+        Ok(NODE::with("EXPR", vec![t, d]))
+    }
+
+    // EXPRDASH -> (+|-) TERM EXPRDASH | ε
+    // or, with right_assoc_additive, EXPRDASH -> (+|-) EXPR | ε
+    // the links of a flat chain are read in a loop and nested afterwards, so
+    // a long sum takes no stack and does not count toward max_depth
+    fn parse_exprdash(&mut self) -> Result<NODE, ParseError> {
+        let mut links = Vec::new();
+        loop {
+            let op = match self.current_token() {
+                TOKEN::PLUS => "PLUS",
+                TOKEN::MINUS => "MINUS",
+                _ => break,
+            };
+            let op = self.token_leaf(op);
+            self.move_to_next_token(); // '+' or '-'
+            if self.right_assoc_additive {
+                let rest = self.nested(Self::parse_expr)?;
+                return Ok(NODE::with("EXPRDASH", vec![op, rest]));
+            }
+            links.push((op, self.parse_term()?));
+        }
+        Ok(nest_links("EXPRDASH", links))
+    }
+
+    // TERM -> UNARY TERMDASH
+    fn parse_term(&mut self) -> Result<NODE, ParseError> {
+        let u = self.parse_unary()?;
+        let d = self.parse_termdash()?;
+        Ok(NODE::with("TERM", vec![u, d]))
+    }
+
+    // TERMDASH -> (*|/|%) UNARY TERMDASH | ε
+    // read in a loop like EXPRDASH
+    fn parse_termdash(&mut self) -> Result<NODE, ParseError> {
+        let mut links = Vec::new();
+        loop {
+            let op = match self.current_token() {
+                TOKEN::STAR => "STAR",
+                TOKEN::SLASH => "SLASH",
+                TOKEN::PERCENT => "PERCENT",
+                _ => break,
+            };
+            let op = self.token_leaf(op);
+            self.move_to_next_token(); // '*', '/' or '%'
+            links.push((op, self.parse_unary()?));
+        }
+        Ok(nest_links("TERMDASH", links))
+    }
+
+    // UNARY -> - UNARY | ! UNARY | POWER
+    // a negation becomes a NEG node and a logical not a NOT node; otherwise the
+    // POWER node is returned as is, so -2 ^ 2 negates the whole power and
+    // !a && b applies ! to a alone
+    fn parse_unary(&mut self) -> Result<NODE, ParseError> {
+        // a pending factor is already this UNARY's POWER operand, whatever follows it
+        if self.pending_factor.is_some() {
+            return self.parse_power();
+        }
+        let (label, op) = match self.current_token() {
+            TOKEN::MINUS => ("NEG", "MINUS"),
+            TOKEN::BANG => ("NOT", "BANG"),
+            _ => return self.parse_power(),
+        };
+        let op = self.token_leaf(op);
+        self.move_to_next_token(); // '-' or '!'
+        let operand = self.nested(Self::parse_unary)?;
+        Ok(NODE::with(label, vec![op, operand]))
+    }
+
+    // POWER -> FACTOR !* POWERDASH
+    // a '!' straight after a factor is postfix factorial, so 5! and (1+2)!
+    // work while !x, met where a factor should start, stays prefix NOT
+    fn parse_power(&mut self) -> Result<NODE, ParseError> {
+        let mut f = self.parse_factor()?;
+        while let TOKEN::BANG = self.current_token() {
+            let end = self.spans.get(self.index).map(|&(_, end)| end);
+            let span = f.span.zip(end).map(|((start, _), end)| (start, end));
+            self.move_to_next_token(); // '!'
+            f = NODE::with("FACTORIAL", vec![f]).at(span);
+        }
+        let d = self.parse_powerdash()?;
+        Ok(NODE::with("POWER", vec![f, d]))
+    }
+
+    // POWERDASH -> (^|**) POWER | ε
+    // recursing on POWER rather than POWERDASH makes ^ right-associative; **
+    // is the same operator spelled differently, and the two mix freely
+    fn parse_powerdash(&mut self) -> Result<NODE, ParseError> {
+        let op = match self.current_token() {
+            TOKEN::CARET => "CARET",
+            TOKEN::POW => "POW",
+            _ => return Ok(NODE::with("POWERDASH", vec![NODE::leaf("EPSILON")])),
+        };
+        let op = self.token_leaf(op);
+        self.move_to_next_token(); // '^' or '**'
+        let rhs = self.nested(Self::parse_power)?;
+        Ok(NODE::with("POWERDASH", vec![op, rhs]))
+    }
+
+    // CALL -> IDENTIFIER ( ARGS? )    ARGS -> EXPR (, EXPR)*
+    // the BOPEN, COMMA and BCLOSE leaves are kept, as FACTOR keeps its parentheses
+    // `name` is the IDENTIFIER leaf, already consumed
+    fn parse_call(&mut self, name: NODE) -> Result<NODE, ParseError> {
+        let opened_at = self.current_position();
+        let mut children = vec![name, self.token_leaf("BOPEN")];
+        self.move_to_next_token(); // '('
+        if !matches!(self.current_token(), TOKEN::BCLOSE) {
+            children.push(self.nested(Self::parse_expr)?);
+            while let TOKEN::COMMA = self.current_token() {
+                children.push(self.token_leaf("COMMA"));
+                self.move_to_next_token(); // ','
+                children.push(self.nested(Self::parse_expr)?);
+            }
+        }
+        if let TOKEN::BCLOSE = self.current_token() {
+            children.push(self.token_leaf("BCLOSE"));
+            self.move_to_next_token();
+        } else {
+            let at = self.current_position();
+            children.push(self.recover(ParseError::MissingClosingParen { opened_at, at })?);
+        }
+        Ok(NODE::with("CALL", children))
+    }
+
+    // FACTOR -> CALL | IDENTIFIER | NUMBER | ( TERNARY ) | [ TERNARY ] | { TERNARY }
+    fn parse_factor(&mut self) -> Result<NODE, ParseError> {
+        if let Some(factor) = self.pending_factor.take() {
+            return Ok(factor);
+        }
+        let at = self.current_position();
+        match self.current_token() {
+            TOKEN::IDENTIFIER(name) if matches!(self.peek_token(), TOKEN::BOPEN) => {
+                let name = self.token_leaf(&format!("IDENTIFIER({})", name));
+                self.move_to_next_token(); // the identifier
+                let call = self.parse_call(name)?;
+                Ok(NODE::with("FACTOR", vec![call]))
+            }
+            TOKEN::IDENTIFIER(name) => {
+                let leaf = self.token_leaf(&format!("IDENTIFIER({})", name));
+                self.move_to_next_token();
+                Ok(NODE::with("FACTOR", vec![leaf]))
+            }
+            TOKEN::NUMBER(n) => {
+                let leaf = self.token_leaf(&format!("NUMBER({})", n));
+                self.move_to_next_token();
+                Ok(NODE::with("FACTOR", vec![leaf]))
+            }
+            TOKEN::BOPEN | TOKEN::SQOPEN | TOKEN::CBOPEN if self.iterative => {
+                self.parse_parens_iteratively()
+            }
+            opener @ (TOKEN::BOPEN | TOKEN::SQOPEN | TOKEN::CBOPEN) => {
+                let opener = opener.clone();
+                let opening = self.token_leaf(opener.kind());
+                self.move_to_next_token();
+                let inside = self.nested(Self::parse_ternary)?;
+                let closing = self.close_group(&opener, at)?;
+                Ok(NODE::with("FACTOR", vec![opening, inside, closing]))
+            }
+            bad => {
+                let error = not_a_factor(bad, at);
+                let leaf = self.recover(error)?;
+                self.synchronize();
+                Ok(leaf)
+            }
+        }
+    }
+
+    // the bracket closing the `opener` at `opened_at`, or an ERROR leaf when
+    // recovering. A closer of the wrong kind is consumed, as it was meant to
+    // end this group
+    fn close_group(
+        &mut self,
+        opener: &TOKEN,
+        opened_at: Option<POSITION>,
+    ) -> Result<NODE, ParseError> {
+        let at = self.current_position();
+        let open = token_char(opener);
+        match self.current_token() {
+            closing if *closing == closer_for(opener) => {
+                let closing = self.token_leaf(closing.kind());
+                self.move_to_next_token();
+                Ok(closing)
+            }
+            found @ (TOKEN::BCLOSE | TOKEN::SQCLOSE | TOKEN::CBCLOSE) => {
+                let found = token_char(found);
+                let error = ParseError::MismatchedBracket { open, found, opened_at, at };
+                let leaf = self.recover(error)?;
+                self.move_to_next_token();
+                Ok(leaf)
+            }
+            _ if *opener == TOKEN::BOPEN => {
+                self.recover(ParseError::MissingClosingParen { opened_at, at })
+            }
+            _ => self.recover(ParseError::UnclosedBracket { open, opened_at, at }),
+        }
+    }
+
+    // FACTOR -> ( TERNARY ) without one recursion per '(': a run of opening
+    // brackets of any kind is pushed on an explicit stack and the innermost TERNARY is
+    // parsed as usual. Each enclosing level is then finished by handing the
+    // FACTOR just closed back as the first factor of its own TERNARY, so the
+    // tree comes out exactly as the recursive branch builds it
+    fn parse_parens_iteratively(&mut self) -> Result<NODE, ParseError> {
+        let mut open = Vec::new();
+        while let opener @ (TOKEN::BOPEN | TOKEN::SQOPEN | TOKEN::CBOPEN) = self.current_token() {
+            let opener = opener.clone();
+            open.push((self.token_leaf(opener.kind()), opener, self.current_position()));
+            self.move_to_next_token();
+        }
+        let mut inside = self.nested(Self::parse_ternary)?;
+        while let Some((opening, opener, opened_at)) = open.pop() {
+            let closing = self.close_group(&opener, opened_at)?;
+            let factor = NODE::with("FACTOR", vec![opening, inside, closing]);
+            if open.is_empty() {
+                return Ok(factor);
+            }
+            self.pending_factor = Some(factor);
+            inside = self.nested(Self::parse_ternary)?;
+        }
+        unreachable!("the loop returns once the outermost '(' is closed")
+    }
+}
+
+// drops EPSILON leaves, then any EXPRDASH/TERMDASH/POWERDASH left without
+// children, and numbers what is left. Works bottom-up from an explicit stack
+// of the nodes being rebuilt, each with the index of its next child and the
+// children kept
+pub fn prune_epsilon(node: &NODE) -> NODE {
+    let mut open: Vec<(&NODE, usize, Vec<NODE>)> = vec![(node, 0, Vec::new())];
+    loop {
+        let (current, next, _) = open.last_mut().unwrap();
+        let child = current.children.get(*next);
+        *next += 1;
+        if let Some(child) = child {
+            if child.label != "EPSILON" {
+                open.push((child, 0, Vec::new()));
+            }
+            continue;
+        }
+        let (current, _, kept) = open.pop().unwrap();
+        let mut pruned = NODE::with(&current.label, kept).at(current.span);
+        match open.last_mut() {
+            None => {
+                assign_ids(&mut pruned);
+                return pruned;
+            }
+            Some(_) if pruned.children.is_empty() && pruned.label.ends_with("DASH") => {}
+            Some((_, _, siblings)) => siblings.push(pruned),
+        }
+    }
+}
+
+// ===== Tree metrics =====
+
+// every node in the tree, leaves included
+pub fn count_nodes(node: &NODE) -> usize {
+    let mut count = 0;
+    let mut pending = vec![node];
+    while let Some(node) = pending.pop() {
+        count += 1;
+        pending.extend(&node.children);
+    }
+    count
+}
+
+// edges on the longest root-to-leaf path; a lone leaf has depth 0
+pub fn tree_depth(node: &NODE) -> usize {
+    let mut deepest = 0;
+    let mut pending = vec![(node, 0)];
+    while let Some((node, depth)) = pending.pop() {
+        deepest = deepest.max(depth);
+        pending.extend(node.children.iter().map(|child| (child, depth + 1)));
+    }
+    deepest
+}
+
+// the most nodes on any one breadth-first level
+pub fn max_width(node: &NODE) -> usize {
+    let mut level = vec![node];
+    let mut widest = 0;
+    while !level.is_empty() {
+        widest = widest.max(level.len());
+        level = level.iter().flat_map(|n| &n.children).collect();
+    }
+    widest
+}
+
+// ===== Visitor =====
+
+// an analysis over a parse tree; walk decides the order, visit sees each node
+pub trait Visitor {
+    fn visit(&mut self, node: &NODE);
+}
+
+// pre-order: a node is visited before its children, children left to right
+pub fn walk<V: Visitor + ?Sized>(node: &NODE, visitor: &mut V) {
+    visitor.visit(node);
+    for child in &node.children {
+        walk(child, visitor);
+    }
+}
+
+// ===== Breadth-first printer: one line per level =====
+
+pub fn bfs_print<W: Write>(root: &NODE, out: &mut W) -> io::Result<()> {
+    let mut q: VecDeque<(&NODE, usize)> = VecDeque::new();
+    q.push_back((root, 0));
+    let mut level = 0usize;
+    let mut line: Vec<&str> = Vec::new();
+
+    while let Some((node, lv)) = q.pop_front() {
+        if lv != level {
+            writeln!(out, "{}", line.join(" "))?;
+            line.clear();
+            level = lv;
+        }
+        line.push(&node.label);
+        for child in &node.children {
+            q.push_back((child, lv + 1));
+        }
+    }
+
+    if !line.is_empty() {
+        writeln!(out, "{}", line.join(" "))?;
+    }
+    Ok(())
+}
+
+// ===== Pre-order printer: one node per line, indented by depth =====
+
+fn print_indented_at<W: Write>(root: &NODE, pad: &str, out: &mut W) -> io::Result<()> {
+    let mut pending = vec![(root, 0)];
+    while let Some((node, depth)) = pending.pop() {
+        writeln!(out, "{}{}", pad.repeat(depth), node.label)?;
+        pending.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
+    }
+    Ok(())
+}
+
+pub const DEFAULT_INDENT: usize = 2;
+
+pub fn print_tree_indented<W: Write>(node: &NODE, out: &mut W) -> io::Result<()> {
+    print_tree_indented_by(node, DEFAULT_INDENT, out)
+}
+
+// like print_tree_indented, with `indent` spaces per level; 0 is taken as 1 so
+// the outline still shows the nesting
+pub fn print_tree_indented_by<W: Write>(node: &NODE, indent: usize, out: &mut W) -> io::Result<()> {
+    print_indented_at(node, &" ".repeat(indent.max(1)), out)
+}
+
+// ===== Post-order printer: children before their parent =====
+
+// one label per line in the order an evaluator would finish each node
+pub fn print_postorder<W: Write>(node: &NODE, out: &mut W) -> io::Result<()> {
+    for child in &node.children {
+        print_postorder(child, out)?;
+    }
+    writeln!(out, "{}", node.label)
+}
+
+// ===== S-expression printer =====
+
+// what the text exporters still have to write: a whole subtree, or the
+// punctuation that goes after one
+enum Piece<'a> {
+    Node(&'a NODE),
+    Text(&'static str),
+}
+
+// renders the tree as (label child1 child2 ...), with leaves as bare labels
+pub fn to_sexpr(root: &NODE) -> String {
+    let mut out = String::new();
+    let mut pending = vec![Piece::Node(root)];
+    while let Some(piece) = pending.pop() {
+        let node = match piece {
+            Piece::Text(text) => {
+                out.push_str(text);
+                continue;
+            }
+            Piece::Node(node) if node.children.is_empty() => {
+                out.push_str(&node.label);
+                continue;
+            }
+            Piece::Node(node) => node,
+        };
+        out.push('(');
+        out.push_str(&node.label);
+        pending.push(Piece::Text(")"));
+        for child in node.children.iter().rev() {
+            pending.push(Piece::Node(child));
+            pending.push(Piece::Text(" "));
+        }
+    }
+    out
+}
+
+// ===== Graphviz DOT export =====
+
+fn dot_escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// renders the tree as a Graphviz digraph, e.g. for `dot -Tpng`. Nodes are
+// named n0, n1, ... in pre-order, the numbering assign_ids gives. Each node is
+// declared before its subtree and the edge to it written after
+pub fn to_dot(root: &NODE) -> String {
+    // a node still to declare (with its parent's number), or the edge to
+    // write once a child's subtree is done
+    enum Step<'a> {
+        Node(&'a NODE, Option<usize>),
+        Edge(usize, usize),
+    }
+    let mut out = String::from("digraph {\n");
+    let mut next_id = 0;
+    let mut pending = vec![Step::Node(root, None)];
+    while let Some(step) = pending.pop() {
+        match step {
+            Step::Edge(parent, child) => out.push_str(&format!("  n{} -> n{};\n", parent, child)),
+            Step::Node(node, parent) => {
+                let id = next_id;
+                next_id += 1;
+                out.push_str(&format!("  n{} [label=\"{}\"];\n", id, dot_escape(&node.label)));
+                if let Some(parent) = parent {
+                    pending.push(Step::Edge(parent, id));
+                }
+                for child in node.children.iter().rev() {
+                    pending.push(Step::Node(child, Some(id)));
+                }
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+// ===== JSON export =====
+
+fn json_escape(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// renders the tree as nested {"id": ..., "label": ..., "children": [...]} objects,
+// with ids numbered in pre-order like assign_ids
+pub fn to_json(root: &NODE) -> String {
+    let mut out = String::new();
+    let mut next_id = 0;
+    let mut pending = vec![Piece::Node(root)];
+    while let Some(piece) = pending.pop() {
+        let node = match piece {
+            Piece::Text(text) => {
+                out.push_str(text);
+                continue;
+            }
+            Piece::Node(node) => node,
+        };
+        let label = json_escape(&node.label);
+        out.push_str(&format!("{{\"id\":{},\"label\":\"{}\",\"children\":[", next_id, label));
+        next_id += 1;
+        pending.push(Piece::Text("]}"));
+        for (i, child) in node.children.iter().enumerate().rev() {
+            pending.push(Piece::Node(child));
+            if i > 0 {
+                pending.push(Piece::Text(","));
+            }
+        }
+    }
+    out
+}
+
+// a parse error as one {"line":N,"col":M,"message":"..."} object, for editors;
+// line and col are null when the tokens carried no positions
+pub fn error_to_json(error: &ParseError) -> String {
+    let (line, col) = match error.position() {
+        Some(p) => (p.line.to_string(), p.column.to_string()),
+        None => ("null".to_string(), "null".to_string()),
+    };
+    let message = json_escape(&error.to_string());
+    format!("{{\"line\":{},\"col\":{},\"message\":\"{}\"}}", line, col, message)
+}
+
+// ===== EVALUATOR =====
+
+#[derive(Debug, PartialEq)]
+pub enum EvalError {
+    UnknownVariable(String),
+    InvalidNumber(String),
+    UnexpectedNode(String),
+    Overflow,
+    UnknownFunction(String),
+    InvalidFactorial(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::UnknownVariable(name) => write!(f, "unknown variable '{}'", name),
+            EvalError::InvalidNumber(n) => write!(f, "invalid number '{}'", n),
+            EvalError::UnexpectedNode(label) => write!(f, "cannot evaluate a {} node", label),
+            EvalError::Overflow => write!(f, "integer overflow"),
+            EvalError::UnknownFunction(name) => write!(f, "unknown function '{}'", name),
+            EvalError::InvalidFactorial(n) => {
+                write!(f, "factorial of {} (it needs a whole number >= 0)", n)
+            }
+        }
+    }
+}
+
+// the result of evaluating: exact while everything involved is an integer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl Value {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Value::Int(n) => n as f64,
+            Value::Float(x) => x,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+// the text inside a leaf label like NUMBER(42), if the label has that kind
+fn leaf_payload<'a>(label: &'a str, kind: &str) -> Option<&'a str> {
+    label.strip_prefix(kind)?.strip_prefix('(')?.strip_suffix(')')
+}
+
+// digits in base `radix`, accumulated as f64 so long literals can't overflow
+fn radix_value(digits: &str, radix: u32) -> Option<f64> {
+    digits.chars().try_fold(0.0, |acc, c| Some(acc * radix as f64 + c.to_digit(radix)? as f64))
+}
+
+// the check behind SCANNER::check_numbers: the literal evaluates without
+// overflowing, and a float stays finite
+fn number_fits(text: &str) -> bool {
+    match literal_value(text) {
+        Ok(Value::Float(x)) => x.is_finite(),
+        Ok(Value::Int(_)) => true,
+        Err(_) => false,
+    }
+}
+
+fn number_value(text: &str) -> Result<f64, EvalError> {
+    let digits = text.replace('_', "");
+    let parsed = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        radix_value(hex, 16)
+    } else if let Some(bin) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        radix_value(bin, 2)
+    } else {
+        digits.parse::<f64>().ok()
+    };
+    parsed.ok_or_else(|| EvalError::InvalidNumber(text.to_string()))
+}
+
+// a NUMBER leaf with no '.' or exponent stays an exact i64; anything else is f64
+fn literal_value(text: &str) -> Result<Value, EvalError> {
+    let digits = text.replace('_', "");
+    let (body, radix) = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        (hex, 16)
+    } else if let Some(bin) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        (bin, 2)
+    } else if digits.contains(['.', 'e', 'E']) {
+        return number_value(text).map(Value::Float);
+    } else {
+        (digits.as_str(), 10)
+    };
+    i64::from_str_radix(body, radix).map(Value::Int).map_err(|e| match e.kind() {
+        IntErrorKind::PosOverflow => EvalError::Overflow,
+        _ => EvalError::InvalidNumber(text.to_string()),
+    })
+}
+
+// integer +, -, *, % and ^ are checked; / stays exact only when it divides evenly
+fn int_arith(op: &str, a: i64, b: i64) -> Result<Value, EvalError> {
+    let exact = match op {
+        "PLUS" => a.checked_add(b),
+        "MINUS" => a.checked_sub(b),
+        "STAR" => a.checked_mul(b),
+        "SLASH" if b == 0 || a.checked_rem(b).is_some_and(|r| r != 0) => {
+            return Ok(Value::Float(a as f64 / b as f64));
+        }
+        "SLASH" => a.checked_div(b),
+        "PERCENT" if b == 0 => return Ok(Value::Float(f64::NAN)),
+        "PERCENT" => a.checked_rem(b),
+        "CARET" | "POW" if b < 0 => return Ok(Value::Float((a as f64).powf(b as f64))),
+        "CARET" | "POW" => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+        other => return Err(EvalError::UnexpectedNode(other.to_string())),
+    };
+    exact.map(Value::Int).ok_or(EvalError::Overflow)
+}
+
+// n! for a whole n >= 0 as an f64; past 170! the result is infinite
+fn float_factorial(x: f64) -> Option<f64> {
+    if x < 0.0 || x.fract() != 0.0 {
+        return None;
+    }
+    Some(if x > 170.0 { f64::INFINITY } else { (1..=x as u64).map(|k| k as f64).product() })
+}
+
+// an integer factorial is checked like the other integer arithmetic
+fn factorial(operand: Value) -> Result<Value, EvalError> {
+    let invalid = || EvalError::InvalidFactorial(operand.to_string());
+    match operand {
+        Value::Int(n) if n < 0 => Err(invalid()),
+        Value::Int(n) => {
+            let product = (1..=n).try_fold(1i64, i64::checked_mul);
+            product.map(Value::Int).ok_or(EvalError::Overflow)
+        }
+        Value::Float(x) => float_factorial(x).map(Value::Float).ok_or_else(invalid),
+    }
+}
+
+// applies a binary operator leaf label; a float on either side makes the result a float
+fn arith(op: &str, lhs: Value, rhs: Value) -> Result<Value, EvalError> {
+    let (a, b) = match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => return int_arith(op, a, b),
+        _ => (lhs.as_f64(), rhs.as_f64()),
+    };
+    Ok(Value::Float(match op {
+        "PLUS" => a + b,
+        "MINUS" => a - b,
+        "STAR" => a * b,
+        "SLASH" => a / b,
+        "PERCENT" => a % b,
+        "CARET" | "POW" => a.powf(b),
+        other => return Err(EvalError::UnexpectedNode(other.to_string())),
+    }))
+}
+
+// integers compare exactly; NaN is unordered, so only != holds for it
+fn compare(op: &str, lhs: Value, rhs: Value) -> Result<bool, EvalError> {
+    let order = match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(&b)),
+        _ => lhs.as_f64().partial_cmp(&rhs.as_f64()),
+    };
+    Ok(match op {
+        "LT" => order == Some(Ordering::Less),
+        "GT" => order == Some(Ordering::Greater),
+        "LE" => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+        "GE" => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+        "EQ" => order == Some(Ordering::Equal),
+        "NE" => order != Some(Ordering::Equal),
+        other => return Err(EvalError::UnexpectedNode(other.to_string())),
+    })
+}
+
+// folds an EXPRDASH/TERMDASH chain onto `acc` from left to right, so
+// 10 - 3 - 2 is (10 - 3) - 2 even though the chain nests to the right.
+// A right-associative EXPRDASH holds the whole rest as one EXPR instead
+fn eval_chain(
+    mut acc: Value,
+    mut dash: &NODE,
+    env: &HashMap<String, f64>,
+) -> Result<Value, EvalError> {
+    loop {
+        match dash.children.as_slice() {
+            [op, operand, more] => {
+                let rhs = eval_value_with(operand, env)?;
+                acc = arith(&op.label, acc, rhs)?;
+                dash = more;
+            }
+            [op, rest] => return arith(&op.label, acc, eval_value_with(rest, env)?),
+            _ => return Ok(acc),
+        }
+    }
+}
+
+// the names eval_with would look up in its env, sorted and without repeats.
+// Assignment targets and function names are not variable reads, so they are
+// left out unless the name is also read somewhere else
+pub fn free_variables(node: &NODE) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    collect_reads(node, &mut names);
+    names
+}
+
+fn collect_reads(node: &NODE, names: &mut BTreeSet<String>) {
+    if let Some(name) = leaf_payload(&node.label, "IDENTIFIER") {
+        names.insert(name.to_string());
+        return;
+    }
+    // ASSIGN and CALL both start with an IDENTIFIER that is not read
+    let skip = matches!(node.label.as_str(), "ASSIGN" | "CALL") as usize;
+    for child in node.children.iter().skip(skip) {
+        collect_reads(child, names);
+    }
+}
+
+// computes the value of a parse tree produced by PARSER
+pub fn eval(node: &NODE) -> Result<f64, EvalError> {
+    eval_with(node, &HashMap::new())
+}
+
+// like eval, but identifiers take their values from `env`
+pub fn eval_with(node: &NODE, env: &HashMap<String, f64>) -> Result<f64, EvalError> {
+    eval_value_with(node, env).map(Value::as_f64)
+}
+
+// like eval, but integer literals and arithmetic on them stay exact i64
+pub fn eval_value(node: &NODE) -> Result<Value, EvalError> {
+    eval_value_with(node, &HashMap::new())
+}
+
+pub fn eval_value_with(node: &NODE, env: &HashMap<String, f64>) -> Result<Value, EvalError> {
+    match (node.label.as_str(), node.children.as_slice()) {
+        ("EXPR" | "TERM", [first, dash]) => eval_chain(eval_value_with(first, env)?, dash, env),
+        ("FACTORIAL", [operand]) => factorial(eval_value_with(operand, env)?),
+        ("NEG", [_, operand]) => match eval_value_with(operand, env)? {
+            Value::Int(n) => n.checked_neg().map(Value::Int).ok_or(EvalError::Overflow),
+            Value::Float(x) => Ok(Value::Float(-x)),
+        },
+        // a true comparison is 1, a false one 0
+        ("COMPARISON", [lhs, op, rhs]) => {
+            let (lhs, rhs) = (eval_value_with(lhs, env)?, eval_value_with(rhs, env)?);
+            Ok(Value::Int(compare(&op.label, lhs, rhs)? as i64))
+        }
+        ("POWER", [base, dash]) => {
+            let base = eval_value_with(base, env)?;
+            match dash.children.as_slice() {
+                [caret, exponent] => arith(&caret.label, base, eval_value_with(exponent, env)?),
+                _ => Ok(base),
+            }
+        }
+        // only the chosen branch is evaluated
+        ("TERNARY", [condition, then, otherwise]) => {
+            let holds = eval_value_with(condition, env)?.as_f64() != 0.0;
+            eval_value_with(if holds { then } else { otherwise }, env)
+        }
+        ("NOT", [_, operand]) => {
+            let operand = eval_value_with(operand, env)?.as_f64();
+            Ok(Value::Int((operand == 0.0) as i64))
+        }
+        // && and || treat any nonzero value as true, give 1 or 0, and skip the
+        // right side once the left decides the result
+        ("CONJUNCTION" | "DISJUNCTION", [lhs, op, rhs]) => {
+            let lhs = eval_value_with(lhs, env)?.as_f64() != 0.0;
+            let result = match op.label.as_str() {
+                "AND" if !lhs => false,
+                "OR" if lhs => true,
+                "AND" | "OR" => eval_value_with(rhs, env)?.as_f64() != 0.0,
+                other => return Err(EvalError::UnexpectedNode(other.to_string())),
+            };
+            Ok(Value::Int(result as i64))
+        }
+        // a program or list is worth its last item; each one is still evaluated
+        ("PROGRAM" | "EXPRLIST", [statements @ .., last]) => {
+            for statement in statements {
+                eval_value_with(statement, env)?;
+            }
+            eval_value_with(last, env)
+        }
+        // env is read-only here, so an assignment just yields the assigned value
+        ("ASSIGN", [_, value]) => eval_value_with(value, env),
+        // no functions are defined yet
+        ("CALL", [callee, ..]) => {
+            let name = leaf_payload(&callee.label, "IDENTIFIER").unwrap_or(&callee.label);
+            Err(EvalError::UnknownFunction(name.to_string()))
+        }
+        ("FACTOR", [leaf]) => eval_value_with(leaf, env),
+        ("FACTOR", [_, inside, _]) => eval_value_with(inside, env),
+        (label, []) => {
+            if let Some(n) = leaf_payload(label, "NUMBER") {
+                literal_value(n)
+            } else if let Some(name) = leaf_payload(label, "IDENTIFIER") {
+                let value = env.get(name).copied().map(Value::Float);
+                value.ok_or_else(|| EvalError::UnknownVariable(name.to_string()))
+            } else {
+                Err(EvalError::UnexpectedNode(label.to_string()))
+            }
+        }
+        (label, _) => Err(EvalError::UnexpectedNode(label.to_string())),
+    }
+}
+
+// ===== AST =====
+
+// the expression with the grammar scaffolding (EXPRDASH, EPSILON, ...) removed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ast {
+    Num(f64),
+    Var(String),
+    Add(Box<Ast>, Box<Ast>),
+    Sub(Box<Ast>, Box<Ast>),
+    Mul(Box<Ast>, Box<Ast>),
+    Div(Box<Ast>, Box<Ast>),
+    Mod(Box<Ast>, Box<Ast>),
+    Pow(Box<Ast>, Box<Ast>),
+    Neg(Box<Ast>),
+    Lt(Box<Ast>, Box<Ast>),
+    Gt(Box<Ast>, Box<Ast>),
+    Le(Box<Ast>, Box<Ast>),
+    Ge(Box<Ast>, Box<Ast>),
+    Eq(Box<Ast>, Box<Ast>),
+    Ne(Box<Ast>, Box<Ast>),
+    Assign(String, Box<Ast>),
+    Program(Vec<Ast>),
+    List(Vec<Ast>),
+    Call(String, Vec<Ast>),
+    And(Box<Ast>, Box<Ast>),
+    Or(Box<Ast>, Box<Ast>),
+    Not(Box<Ast>),
+    Factorial(Box<Ast>),
+    Ternary(Box<Ast>, Box<Ast>, Box<Ast>),
+}
+
+// why to_ast could not turn a tree into an Ast
+#[derive(Debug, Clone, PartialEq)]
+pub enum AstError {
+    // an ERROR leaf, which parse_recovering leaves where the input was bad
+    ErrorLeaf,
+    // a node of this label not shaped like PARSER output, e.g. in a hand-built tree
+    Malformed(String),
+}
+
+impl fmt::Display for AstError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AstError::ErrorLeaf => write!(f, "the tree has an ERROR leaf in place of bad input"),
+            AstError::Malformed(label) => {
+                write!(f, "{} node is not shaped like PARSER output", label)
+            }
+        }
+    }
+}
+
+fn malformed<T>(node: &NODE) -> Result<T, AstError> {
+    Err(AstError::Malformed(node.label.clone()))
+}
+
+// `lhs op rhs` for a PLUS, MINUS, STAR, SLASH or PERCENT leaf
+fn chain_link(op: &NODE, lhs: Ast, rhs: Ast) -> Result<Ast, AstError> {
+    let (lhs, rhs) = (Box::new(lhs), Box::new(rhs));
+    Ok(match op.label.as_str() {
+        "PLUS" => Ast::Add(lhs, rhs),
+        "MINUS" => Ast::Sub(lhs, rhs),
+        "STAR" => Ast::Mul(lhs, rhs),
+        "SLASH" => Ast::Div(lhs, rhs),
+        "PERCENT" => Ast::Mod(lhs, rhs),
+        _ => return malformed(op),
+    })
+}
+
+// collapses an EXPRDASH/TERMDASH chain into left-associated binary nodes,
+// or a right-associative EXPRDASH into one node over the rest
+fn chain_to_ast(mut acc: Ast, mut dash: &NODE) -> Result<Ast, AstError> {
+    loop {
+        match dash.children.as_slice() {
+            [op, operand, more] => {
+                acc = chain_link(op, acc, to_ast(operand)?)?;
+                dash = more;
+            }
+            [op, rest] => return chain_link(op, acc, to_ast(rest)?),
+            _ => return Ok(acc),
+        }
+    }
+}
+
+// converts a parse tree from PARSER into an Ast. A tree from parse_recovering
+// that still holds an ERROR leaf fails with ErrorLeaf, and any shape PARSER
+// does not produce with Malformed
+pub fn to_ast(node: &NODE) -> Result<Ast, AstError> {
+    let boxed = |node: &NODE| to_ast(node).map(Box::new);
+    Ok(match (node.label.as_str(), node.children.as_slice()) {
+        ("EXPR" | "TERM", [first, dash]) => return chain_to_ast(to_ast(first)?, dash),
+        ("NEG", [_, operand]) => Ast::Neg(boxed(operand)?),
+        ("NOT", [_, operand]) => Ast::Not(boxed(operand)?),
+        ("FACTORIAL", [operand]) => Ast::Factorial(boxed(operand)?),
+        ("TERNARY", [condition, then, otherwise]) => {
+            Ast::Ternary(boxed(condition)?, boxed(then)?, boxed(otherwise)?)
+        }
+        ("COMPARISON", [lhs, op, rhs]) => {
+            let (lhs, rhs) = (boxed(lhs)?, boxed(rhs)?);
+            match op.label.as_str() {
+                "LT" => Ast::Lt(lhs, rhs),
+                "GT" => Ast::Gt(lhs, rhs),
+                "LE" => Ast::Le(lhs, rhs),
+                "GE" => Ast::Ge(lhs, rhs),
+                "EQ" => Ast::Eq(lhs, rhs),
+                "NE" => Ast::Ne(lhs, rhs),
+                _ => return malformed(op),
+            }
+        }
+        ("POWER", [base, dash]) => match dash.children.as_slice() {
+            [_, exponent] => Ast::Pow(boxed(base)?, boxed(exponent)?),
+            _ => return to_ast(base),
+        },
+        ("ASSIGN", [target, value]) => match leaf_payload(&target.label, "IDENTIFIER") {
+            Some(name) => Ast::Assign(name.to_string(), boxed(value)?),
+            None => return malformed(target),
+        },
+        ("CONJUNCTION", [lhs, _, rhs]) => Ast::And(boxed(lhs)?, boxed(rhs)?),
+        ("DISJUNCTION", [lhs, _, rhs]) => Ast::Or(boxed(lhs)?, boxed(rhs)?),
+        ("PROGRAM", statements) if !statements.is_empty() => {
+            Ast::Program(statements.iter().map(to_ast).collect::<Result<_, _>>()?)
+        }
+        ("EXPRLIST", items) if !items.is_empty() => {
+            Ast::List(items.iter().map(to_ast).collect::<Result<_, _>>()?)
+        }
+        ("CALL", [callee, rest @ ..]) => match leaf_payload(&callee.label, "IDENTIFIER") {
+            Some(name) => {
+                let is_arg = |n: &&NODE| !matches!(n.label.as_str(), "BOPEN" | "COMMA" | "BCLOSE");
+                let args = rest.iter().filter(is_arg).map(to_ast);
+                Ast::Call(name.to_string(), args.collect::<Result<_, _>>()?)
+            }
+            None => return malformed(callee),
+        },
+        ("FACTOR", [leaf]) => return to_ast(leaf),
+        ("FACTOR", [_, inside, _]) => return to_ast(inside),
+        ("ERROR", []) => return Err(AstError::ErrorLeaf),
+        (label, []) => {
+            if let Some(n) = leaf_payload(label, "NUMBER") {
+                match number_value(n) {
+                    Ok(value) => Ast::Num(value),
+                    Err(_) => return malformed(node),
+                }
+            } else if let Some(name) = leaf_payload(label, "IDENTIFIER") {
+                Ast::Var(name.to_string())
+            } else {
+                return malformed(node);
+            }
+        }
+        _ => return malformed(node),
+    })
+}
+
+// the expression as infix text with the fewest parentheses that keep its
+// meaning, e.g. Mul(Add(1, 2), 3) is `(1 + 2) * 3`; same as Ast's Display
+pub fn ast_to_string(ast: &Ast) -> String {
+    ast.to_string()
+}
+
+// renders the tree back into an infix expression like `(1 + 2) * 3`,
+// with parentheses only where precedence or associativity needs them; fails
+// like to_ast on a tree it cannot convert
+pub fn to_infix(node: &NODE) -> Result<String, AstError> {
+    to_ast(node).map(|ast| ast_to_string(&ast))
+}
+
+// the Ast as a NODE tree, so the printers can show it: operators become
+// nodes named after their variant (Add, Neg, ...) over their operands, and
+// leaves read Num(2) or Var(x). Assign(x) and Call(f) carry the name in the label.
+// The tree comes back numbered by assign_ids
+pub fn ast_to_node(ast: &Ast) -> NODE {
+    let mut tree = ast_node(ast);
+    assign_ids(&mut tree);
+    tree
+}
+
+fn ast_node(ast: &Ast) -> NODE {
+    let node = |label: &str, operands: &[&Ast]| {
+        NODE::with(label, operands.iter().map(|a| ast_node(a)).collect())
+    };
+    let list =
+        |label: &str, items: &[Ast]| NODE::with(label, items.iter().map(ast_node).collect());
+    match ast {
+        Ast::Num(n) => NODE::leaf(&format!("Num({})", n)),
+        Ast::Var(name) => NODE::leaf(&format!("Var({})", name)),
+        Ast::Add(l, r) => node("Add", &[l, r]),
+        Ast::Sub(l, r) => node("Sub", &[l, r]),
+        Ast::Mul(l, r) => node("Mul", &[l, r]),
+        Ast::Div(l, r) => node("Div", &[l, r]),
+        Ast::Mod(l, r) => node("Mod", &[l, r]),
+        Ast::Pow(l, r) => node("Pow", &[l, r]),
+        Ast::Neg(operand) => node("Neg", &[operand]),
+        Ast::Lt(l, r) => node("Lt", &[l, r]),
+        Ast::Gt(l, r) => node("Gt", &[l, r]),
+        Ast::Le(l, r) => node("Le", &[l, r]),
+        Ast::Ge(l, r) => node("Ge", &[l, r]),
+        Ast::Eq(l, r) => node("Eq", &[l, r]),
+        Ast::Ne(l, r) => node("Ne", &[l, r]),
+        Ast::Assign(name, value) => node(&format!("Assign({})", name), &[value]),
+        Ast::Program(statements) => list("Program", statements),
+        Ast::List(items) => list("List", items),
+        Ast::Call(name, args) => list(&format!("Call({})", name), args),
+        Ast::And(l, r) => node("And", &[l, r]),
+        Ast::Or(l, r) => node("Or", &[l, r]),
+        Ast::Not(operand) => node("Not", &[operand]),
+        Ast::Factorial(operand) => node("Factorial", &[operand]),
+        Ast::Ternary(c, t, e) => node("Ternary", &[c, t, e]),
+    }
+}
+
+const TERNARY_PRECEDENCE: u8 = 1;
+const COMPARISON_PRECEDENCE: u8 = 4;
+const UNARY_PRECEDENCE: u8 = 7;
+const POSTFIX_PRECEDENCE: u8 = 9;
+
+impl Ast {
+    // how tightly the node's operator binds; leaves bind tightest
+    fn precedence(&self) -> u8 {
+        match self {
+            Ast::Num(n) if *n < 0.0 => UNARY_PRECEDENCE, // prints with a leading '-'
+            Ast::Num(_) | Ast::Var(_) | Ast::Call(..) | Ast::Factorial(_) => POSTFIX_PRECEDENCE,
+            Ast::Pow(..) => 8,
+            Ast::Neg(_) | Ast::Not(_) => UNARY_PRECEDENCE,
+            Ast::Mul(..) | Ast::Div(..) | Ast::Mod(..) => 6,
+            Ast::Add(..) | Ast::Sub(..) => 5,
+            Ast::Lt(..) | Ast::Gt(..) | Ast::Le(..) | Ast::Ge(..) | Ast::Eq(..) | Ast::Ne(..) => {
+                COMPARISON_PRECEDENCE
+            }
+            Ast::And(..) => 3,
+            Ast::Or(..) => 2,
+            Ast::Ternary(..) => TERNARY_PRECEDENCE,
+            Ast::Assign(..) | Ast::Program(_) | Ast::List(_) => 0,
+        }
+    }
+
+    // (lhs, operator, rhs) for the binary variants
+    fn binary(&self) -> Option<(&Ast, &'static str, &Ast)> {
+        let (lhs, op, rhs) = match self {
+            Ast::Add(l, r) => (l, "+", r),
+            Ast::Sub(l, r) => (l, "-", r),
+            Ast::Mul(l, r) => (l, "*", r),
+            Ast::Div(l, r) => (l, "/", r),
+            Ast::Mod(l, r) => (l, "%", r),
+            Ast::Pow(l, r) => (l, "^", r),
+            Ast::Lt(l, r) => (l, "<", r),
+            Ast::Gt(l, r) => (l, ">", r),
+            Ast::Le(l, r) => (l, "<=", r),
+            Ast::Ge(l, r) => (l, ">=", r),
+            Ast::Eq(l, r) => (l, "==", r),
+            Ast::Ne(l, r) => (l, "!=", r),
+            Ast::And(l, r) => (l, "&&", r),
+            Ast::Or(l, r) => (l, "||", r),
+            _ => return None,
+        };
+        Some((lhs, op, rhs))
+    }
+}
+
+fn write_operand(f: &mut fmt::Formatter, operand: &Ast, parens: bool) -> fmt::Result {
+    if parens { write!(f, "({})", operand) } else { write!(f, "{}", operand) }
+}
+
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((lhs, op, rhs)) = self.binary() {
+            let p = self.precedence();
+            let (lhs_parens, rhs_parens) = match self {
+                // ^ groups to the right, and its operands can't start with '-'
+                Ast::Pow(..) => (lhs.precedence() <= p, rhs.precedence() < p),
+                // comparisons don't chain, so either side that is one needs parens
+                _ if p == COMPARISON_PRECEDENCE => (lhs.precedence() <= p, rhs.precedence() <= p),
+                _ => (lhs.precedence() < p, rhs.precedence() <= p),
+            };
+            write_operand(f, lhs, lhs_parens)?;
+            write!(f, " {} ", op)?;
+            return write_operand(f, rhs, rhs_parens);
+        }
+        match self {
+            Ast::Num(n) => write!(f, "{}", n),
+            Ast::Var(name) => write!(f, "{}", name),
+            Ast::Assign(name, value) => write!(f, "{} = {}", name, value),
+            Ast::Program(statements) => {
+                let printed: Vec<String> = statements.iter().map(Ast::to_string).collect();
+                write!(f, "{}", printed.join("; "))
+            }
+            Ast::List(items) => {
+                let printed: Vec<String> = items.iter().map(Ast::to_string).collect();
+                write!(f, "{}", printed.join(", "))
+            }
+            Ast::Call(name, args) => {
+                let printed: Vec<String> = args.iter().map(Ast::to_string).collect();
+                write!(f, "{}({})", name, printed.join(", "))
+            }
+            // the condition is an OR, so only a nested ternary there needs parens
+            Ast::Ternary(condition, then, otherwise) => {
+                write_operand(f, condition, condition.precedence() <= TERNARY_PRECEDENCE)?;
+                write!(f, " ? {} : {}", then, otherwise)
+            }
+            // only a leaf, call or another factorial goes bare before the '!'
+            Ast::Factorial(operand) => {
+                write_operand(f, operand, operand.precedence() < POSTFIX_PRECEDENCE)?;
+                write!(f, "!")
+            }
+            Ast::Neg(operand) | Ast::Not(operand) => {
+                write!(f, "{}", if let Ast::Neg(_) = self { "-" } else { "!" })?;
+                write_operand(f, operand, operand.precedence() < UNARY_PRECEDENCE)
+            }
+            _ => unreachable!("binary variants are printed above"),
+        }
+    }
+}
+
+// ===== CONSTANT FOLDING =====
+
+fn truth(holds: bool) -> f64 {
+    if holds { 1.0 } else { 0.0 }
+}
+
+// folds both sides, then computes the node if both became numbers
+fn fold_binary(
+    lhs: Ast,
+    rhs: Ast,
+    value: fn(f64, f64) -> f64,
+    build: fn(Box<Ast>, Box<Ast>) -> Ast,
+) -> Ast {
+    match (fold_constants(lhs), fold_constants(rhs)) {
+        (Ast::Num(a), Ast::Num(b)) => Ast::Num(value(a, b)),
+        (lhs, rhs) => build(Box::new(lhs), Box::new(rhs)),
+    }
+}
+
+// the only identities applied: x + 0, 0 + x, x - 0, x * 1, 1 * x, x / 1, x ^ 1.
+// x * 0 is left alone, since x could be NaN or infinite
+fn drop_identity(ast: Ast) -> Ast {
+    let is = |side: &Ast, n: f64| *side == Ast::Num(n);
+    match ast {
+        Ast::Add(l, r) if is(&l, 0.0) => *r,
+        Ast::Add(l, r) | Ast::Sub(l, r) if is(&r, 0.0) => *l,
+        Ast::Mul(l, r) if is(&l, 1.0) => *r,
+        Ast::Mul(l, r) | Ast::Div(l, r) | Ast::Pow(l, r) if is(&r, 1.0) => *l,
+        other => other,
+    }
+}
+
+// evaluates every subtree whose operands are all numbers, in f64 (a true
+// comparison is 1), then drops identity operations. Ast numbers are all f64,
+// so this is not eval's checked i64 arithmetic: 2 ^ 63 folds to 9.2e18 where
+// eval fails with Overflow
+pub fn fold_constants(ast: Ast) -> Ast {
+    let folded = match ast {
+        Ast::Num(_) | Ast::Var(_) => return ast,
+        Ast::Assign(name, value) => Ast::Assign(name, Box::new(fold_constants(*value))),
+        Ast::Program(statements) => {
+            Ast::Program(statements.into_iter().map(fold_constants).collect())
+        }
+        Ast::List(items) => Ast::List(items.into_iter().map(fold_constants).collect()),
+        Ast::Call(name, args) => Ast::Call(name, args.into_iter().map(fold_constants).collect()),
+        Ast::Neg(operand) => match fold_constants(*operand) {
+            Ast::Num(n) => Ast::Num(-n),
+            other => Ast::Neg(Box::new(other)),
+        },
+        Ast::Ternary(condition, then, otherwise) => match fold_constants(*condition) {
+            Ast::Num(n) => fold_constants(if n != 0.0 { *then } else { *otherwise }),
+            condition => Ast::Ternary(
+                Box::new(condition),
+                Box::new(fold_constants(*then)),
+                Box::new(fold_constants(*otherwise)),
+            ),
+        },
+        Ast::Not(operand) => match fold_constants(*operand) {
+            Ast::Num(n) => Ast::Num(truth(n == 0.0)),
+            other => Ast::Not(Box::new(other)),
+        },
+        // a negative or fractional operand is left for eval to report
+        Ast::Factorial(operand) => match fold_constants(*operand) {
+            Ast::Num(n) => match float_factorial(n) {
+                Some(value) => Ast::Num(value),
+                None => Ast::Factorial(Box::new(Ast::Num(n))),
+            },
+            other => Ast::Factorial(Box::new(other)),
+        },
+        Ast::Add(l, r) => fold_binary(*l, *r, |a, b| a + b, Ast::Add),
+        Ast::Sub(l, r) => fold_binary(*l, *r, |a, b| a - b, Ast::Sub),
+        Ast::Mul(l, r) => fold_binary(*l, *r, |a, b| a * b, Ast::Mul),
+        Ast::Div(l, r) => fold_binary(*l, *r, |a, b| a / b, Ast::Div),
+        Ast::Mod(l, r) => fold_binary(*l, *r, |a, b| a % b, Ast::Mod),
+        Ast::Pow(l, r) => fold_binary(*l, *r, f64::powf, Ast::Pow),
+        Ast::Lt(l, r) => fold_binary(*l, *r, |a, b| truth(a < b), Ast::Lt),
+        Ast::Gt(l, r) => fold_binary(*l, *r, |a, b| truth(a > b), Ast::Gt),
+        Ast::Le(l, r) => fold_binary(*l, *r, |a, b| truth(a <= b), Ast::Le),
+        Ast::Ge(l, r) => fold_binary(*l, *r, |a, b| truth(a >= b), Ast::Ge),
+        Ast::Eq(l, r) => fold_binary(*l, *r, |a, b| truth(a == b), Ast::Eq),
+        Ast::Ne(l, r) => fold_binary(*l, *r, |a, b| truth(a != b), Ast::Ne),
+        Ast::And(l, r) => fold_binary(*l, *r, |a, b| truth(a != 0.0 && b != 0.0), Ast::And),
+        Ast::Or(l, r) => fold_binary(*l, *r, |a, b| truth(a != 0.0 || b != 0.0), Ast::Or),
+    };
+    drop_identity(folded)
+}
+
+// ===== PUBLIC API =====
+
+// an error as the CLI reports it, followed (when it has a position) by the
+// source line it is on and a '^' under its column, the way rustc does:
+//   Error at line 1, column 5: expected a factor, found ')'
+//   1 + )
+//       ^
+// `source` is the whole input the positions count lines in
+pub fn render_error(error: &ParseError, source: &str) -> String {
+    let line = error.position().and_then(|p| source.lines().nth(p.line.saturating_sub(1)));
+    render_error_line(error, line.unwrap_or(""))
+}
+
+// render_error when the caller already has the right line of source
+fn render_error_line(error: &ParseError, line: &str) -> String {
+    let Some(p) = error.position() else {
+        return format!("Error: {}", error);
+    };
+    // tabs are copied so the caret lines up however wide the terminal draws
+    // them; past the end of the line (at EOF) the padding is spaces
+    let pad: String = line
+        .chars()
+        .chain(std::iter::repeat(' '))
+        .take(p.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    format!("Error at line {}, column {}: {}\n{}\n{}^", p.line, p.column, error, line, pad)
+}
+
+// scan a whole string into tokens, ending with EOF; the first ERROR token
+// the scanner produces becomes the Err instead
+pub fn tokenize(input: &str) -> Result<Vec<TOKEN>, ScanError> {
+    first_scan_error(SCANNER::constructor(input.to_string()))
+}
+
+// like tokenize, but a number literal that overflows is an error too
+pub fn tokenize_checked(input: &str) -> Result<Vec<TOKEN>, ScanError> {
+    first_scan_error(SCANNER::constructor(input.to_string()).check_numbers(true))
+}
+
+fn first_scan_error(mut scanner: SCANNER) -> Result<Vec<TOKEN>, ScanError> {
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    for (tok, at) in tokens.iter().zip(&positions) {
+        match tok {
+            // every '"' opens a string, so ERROR('"') can only mean a missing closing quote
+            TOKEN::ERROR('"') => return Err(ScanError::UnterminatedString { at: *at }),
+            TOKEN::ERROR(c) => return Err(ScanError::InvalidCharacter { found: *c, at: *at }),
+            TOKEN::UNCLOSEDCOMMENT => return Err(ScanError::UnterminatedComment { at: *at }),
+            TOKEN::OVERFLOW(text) => {
+                return Err(ScanError::NumberOutOfRange { text: text.clone(), at: *at });
+            }
+            _ => {}
+        }
+    }
+    Ok(tokens)
+}
+
+// how often each kind of token occurs in `tokens`, keyed by TOKEN::kind;
+// EOF is left out, so the counts sum to the number of real tokens
+pub fn token_histogram(tokens: &[TOKEN]) -> HashMap<&'static str, usize> {
+    let mut counts = HashMap::new();
+    for tok in tokens.iter().filter(|tok| **tok != TOKEN::EOF) {
+        *counts.entry(tok.kind()).or_insert(0) += 1;
+    }
+    counts
+}
+
+// like SCANNER's tokens, but identifiers and numbers are slices of `input`
+// rather than fresh Strings; ends with EOF, and bad characters stay ERROR tokens
+pub fn tokenize_borrowed<'a>(input: &'a str) -> Vec<Token<'a>> {
+    let mut scanner = SCANNER::over(Cow::Borrowed(input));
+    let mut tokens = Vec::new();
+    while let Some(lexeme) = scanner.next_lexeme() {
+        let text = &input[scanner.token_start.offset..scanner.byte_index];
+        tokens.push(match lexeme {
+            Lexeme::Identifier => Token::Identifier(text),
+            Lexeme::Number => Token::Number(text),
+            Lexeme::Other(tok) => Token::Other(tok),
+        });
+    }
+    tokens.push(Token::Other(TOKEN::EOF));
+    tokens
+}
+
+// parse a token stream as one complete expression
+pub fn parse(tokens: Vec<TOKEN>) -> Result<NODE, ParseError> {
+    PARSER::constructor(tokens).parse()
+}
+
+// scan and parse the first line of `input` as one expression, with positions
+// in any error and spans on the nodes; anything after the expression on that line is an error, and
+// later lines are not read
+pub fn parse_str(input: &str) -> Result<NODE, ParseError> {
+    let line = input.lines().next().unwrap_or("");
+    let (tokens, positions, spans) = SCANNER::constructor(line.to_string()).tokenize_with_spans();
+    PARSER::constructor(tokens).with_positions(positions).with_spans(spans).parse()
+}
+
+// parse a token stream as far as possible, collecting errors instead of
+// stopping at the first; see PARSER::parse_recovering
+pub fn parse_recovering(tokens: Vec<TOKEN>) -> (NODE, Vec<ParseError>) {
+    PARSER::constructor(tokens).parse_recovering()
+}
+
+// ===== LINE DRIVER =====
+
+// how each parsed line is printed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Bfs,
+    Indent,
+    Sexpr,
+    Dot,
+    Json,
+}
+
+impl Format {
+    pub const NAMES: [&'static str; 5] = ["bfs", "indent", "sexpr", "dot", "json"];
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "bfs" => Ok(Format::Bfs),
+            "indent" => Ok(Format::Indent),
+            "sexpr" => Ok(Format::Sexpr),
+            "dot" => Ok(Format::Dot),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format '{}' (expected one of: {})",
+                name,
+                Format::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+// settings for process_lines, filled in from the command line
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub no_epsilon: bool,
+    pub format: Format,
+    // dump the scanner's tokens instead of parsing
+    pub tokens: bool,
+    // print token, node and depth counts instead of the tree
+    pub count: bool,
+    // fail lines with trailing input instead of warning and parsing the prefix
+    pub strict: bool,
+    // spaces per level for Format::Indent
+    pub indent: usize,
+    // passed to PARSER::max_depth
+    pub max_depth: usize,
+    // color the --tokens dump with ANSI escapes; main turns it on for a terminal
+    pub color: bool,
+    // leave out the blank line after each line's output
+    pub no_separator: bool,
+    // lint mode: print nothing for lines that parse, only the ones that fail
+    pub check: bool,
+    // count token kinds over the whole input instead of parsing
+    pub histogram: bool,
+    // print each line's Ast (via ast_to_node) instead of its parse tree
+    pub ast: bool,
+    // show how much of the input is done, as a percentage on `err`
+    pub progress: bool,
+    // report parse errors with render_error's source line and caret
+    pub caret: bool,
+    // report parse errors as error_to_json objects, one per line; wins over caret
+    pub json_errors: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            no_epsilon: false,
+            format: Format::default(),
+            tokens: false,
+            count: false,
+            strict: false,
+            indent: DEFAULT_INDENT,
+            max_depth: DEFAULT_MAX_DEPTH,
+            color: false,
+            no_separator: false,
+            check: false,
+            histogram: false,
+            ast: false,
+            progress: false,
+            caret: false,
+            json_errors: false,
+        }
+    }
+}
+
+// wraps `text` in the ANSI color for its kind of token: yellow numbers, cyan
+// identifiers, green strings, red errors, magenta operators and punctuation.
+// EOF is left plain
+fn paint(tok: &TOKEN, text: &str) -> String {
+    let code = match tok {
+        TOKEN::NUMBER(_) => 33,
+        TOKEN::IDENTIFIER(_) => 36,
+        TOKEN::STRING(_) => 32,
+        TOKEN::ERROR(_) | TOKEN::OVERFLOW(_) | TOKEN::UNCLOSEDCOMMENT => 31,
+        TOKEN::EOF => return text.to_string(),
+        _ => 35,
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+// with --caret, `line` (the source of the line that failed) is shown as well
+fn report_parse_error<E: Write>(
+    err: &mut E,
+    error: &ParseError,
+    line: &str,
+    options: &Options,
+) -> io::Result<()> {
+    if options.json_errors {
+        return writeln!(err, "{}", error_to_json(error));
+    }
+    if options.caret {
+        return writeln!(err, "{}", render_error_line(error, line));
+    }
+    match error.position() {
+        Some(p) => writeln!(err, "Error at line {}, column {}: {}", p.line, p.column, error),
+        None => writeln!(err, "Error: {}", error),
+    }
+}
+
+// the blank line that separates one line's output from the next
+fn end_of_line<W: Write>(out: &mut W, options: &Options) -> io::Result<()> {
+    if options.no_separator { Ok(()) } else { writeln!(out) }
+}
+
+// what process_line made of one line of input
+#[derive(PartialEq)]
+enum LineOutcome {
+    Blank,
+    Parsed,
+    Failed,
+}
+
+// a parser for one line's tokens, set up the way `options` asks
+fn line_parser(tokens: Vec<TOKEN>, positions: Vec<POSITION>, options: &Options) -> PARSER {
+    PARSER::constructor(tokens)
+        .with_positions(positions)
+        .strict(options.strict)
+        .max_depth(options.max_depth)
+}
+
+// --check: parses like process_line but stays silent unless the line fails,
+// in which case the line itself goes to `err` ahead of its error
+fn check_line<E: Write>(
+    scanner: &mut SCANNER,
+    line: &str,
+    line_number: usize,
+    options: &Options,
+    err: &mut E,
+) -> io::Result<LineOutcome> {
+    scanner.reset(line);
+    scanner.line = line_number;
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    if let [TOKEN::EOF] = tokens.as_slice() {
+        return Ok(LineOutcome::Blank);
+    }
+    match line_parser(tokens, positions, options).parse() {
+        Ok(_) => Ok(LineOutcome::Parsed),
+        Err(error) => {
+            // --caret shows the line under the message already, and
+            // --json-errors keeps every line of `err` a JSON object
+            if !options.caret && !options.json_errors {
+                writeln!(err, "{}: {}", line_number, line)?;
+            }
+            report_parse_error(err, &error, line, options)?;
+            Ok(LineOutcome::Failed)
+        }
+    }
+}
+
+// scans, parses and prints a single line; `line_number` is used for error positions.
+// A line with no tokens (blank, whitespace or comments only) prints as a blank line.
+// A parse error is reported on `err`. `scanner` is reset onto the line, so
+// the caller can keep one for a whole run
+fn process_line<W: Write, E: Write>(
+    scanner: &mut SCANNER,
+    line: &str,
+    line_number: usize,
+    options: &Options,
+    out: &mut W,
+    err: &mut E,
+) -> io::Result<LineOutcome> {
+    if options.check {
+        return check_line(scanner, line, line_number, options, err);
+    }
+    scanner.reset(line);
+    scanner.line = line_number;
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    if options.tokens {
+        // one "line:column token" per line, EOF included
+        for (tok, at) in tokens.iter().zip(&positions) {
+            let text = tok.to_string();
+            let text = if options.color { paint(tok, &text) } else { text };
+            writeln!(out, "{}:{} {}", at.line, at.column, text)?;
+        }
+        end_of_line(out, options)?;
+        let blank = tokens.len() == 1;
+        return Ok(if blank { LineOutcome::Blank } else { LineOutcome::Parsed });
+    }
+    // nothing but whitespace or comments: keep the blank line, skip the parse
+    if let [TOKEN::EOF] = tokens.as_slice() {
+        end_of_line(out, options)?;
+        return Ok(LineOutcome::Blank);
+    }
+    let token_count = tokens.len() - 1; // not counting EOF
+    let mut parser = line_parser(tokens, positions, options);
+
+    let tree = match parser.parse() {
+        Ok(tree) => tree,
+        Err(error) => {
+            report_parse_error(err, &error, line, options)?;
+            return Ok(LineOutcome::Failed);
+        }
+    };
+    if let Some(ignored) = parser.leftover() {
+        // only reachable when lenient: strict parsing already failed above
+        if let Some(p) = ignored.position() {
+            writeln!(err, "Warning at line {}, column {}: ignoring {}", p.line, p.column, ignored)?;
+        }
+    }
+    // --ast prints the Ast instead, which has no EPSILON leaves to prune
+    // (and to_ast needs the unpruned tree)
+    let mut tree = if options.ast {
+        ast_to_node(&to_ast(&tree).expect("PARSER::parse output converts to an Ast"))
+    } else if options.no_epsilon {
+        prune_epsilon(&tree)
+    } else {
+        tree
+    };
+    if options.count {
+        let (nodes, depth) = (count_nodes(&tree), tree_depth(&tree));
+        writeln!(out, "tokens: {}, nodes: {}, depth: {}", token_count, nodes, depth)?;
+        return Ok(LineOutcome::Parsed);
+    }
+    // each expression of a top-level `a, b, c` list is printed as its own tree
+    let trees = if matches!(tree.label.as_str(), "EXPRLIST" | "List") {
+        std::mem::take(&mut tree.children)
+    } else {
+        vec![tree]
+    };
+    for tree in &trees {
+        match options.format {
+            Format::Bfs => bfs_print(tree, out)?,
+            Format::Indent => print_tree_indented_by(tree, options.indent, out)?,
+            Format::Sexpr => writeln!(out, "{}", to_sexpr(tree))?,
+            Format::Dot => write!(out, "{}", to_dot(tree))?,
+            Format::Json => writeln!(out, "{}", to_json(tree))?,
+        }
+        end_of_line(out, options)?;
+    }
+    Ok(LineOutcome::Parsed)
+}
+
+// how many expression lines a run saw and how many of them failed to parse;
+// lines with no tokens are not counted
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tally {
+    pub lines: usize,
+    pub failed: usize,
+}
+
+impl Tally {
+    pub fn all_passed(&self) -> bool {
+        self.failed == 0
+    }
+
+    pub fn add(&mut self, other: Tally) {
+        self.lines += other.lines;
+        self.failed += other.failed;
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} of {} lines failed", self.failed, self.lines)
+    }
+}
+
+// scans, parses and prints every line of `input`, one expression per line.
+// A line that fails to parse is reported on `err` and the run moves on to
+// the next one; the returned Tally says how many failed.
+pub fn process_lines<R: BufRead, W: Write, E: Write>(
+    mut input: R,
+    options: &Options,
+    out: &mut W,
+    err: &mut E,
+) -> io::Result<Tally> {
+    if options.histogram {
+        return print_histogram(input, out);
+    }
+    if options.progress {
+        // a percentage needs the line count up front, so read everything first
+        let mut text = String::new();
+        input.read_to_string(&mut text)?;
+        let total = text.lines().count();
+        let lines = text.lines().map(|line| Ok(line.to_string()));
+        return process_each(lines, Some(total), options, out, err);
+    }
+    process_each(input.lines(), None, options, out, err)
+}
+
+// how far through `total` lines the run is after `done` of them, rounded
+// down; an empty input is already done
+pub fn percent_done(done: usize, total: usize) -> usize {
+    (done.min(total) * 100).checked_div(total).unwrap_or(100)
+}
+
+// process_lines' loop. With a `total`, the percentage is written to `err` as
+// "N%\r" whenever it goes up a whole percent, so a big file redraws it at most
+// 101 times; the '\r' lets the next error message write over it
+fn process_each<W: Write, E: Write>(
+    lines: impl Iterator<Item = io::Result<String>>,
+    total: Option<usize>,
+    options: &Options,
+    out: &mut W,
+    err: &mut E,
+) -> io::Result<Tally> {
+    let mut tally = Tally::default();
+    let mut shown = None;
+    let mut scanner = SCANNER::constructor(String::new());
+    for (n, line) in lines.enumerate() {
+        match process_line(&mut scanner, &line?, n + 1, options, out, err)? {
+            LineOutcome::Blank => {}
+            LineOutcome::Parsed => tally.lines += 1,
+            LineOutcome::Failed => {
+                tally.lines += 1;
+                tally.failed += 1;
+            }
+        }
+        if let Some(total) = total {
+            let percent = percent_done(n + 1, total);
+            if shown != Some(percent) {
+                write!(err, "{}%\r", percent)?;
+                err.flush()?;
+                shown = Some(percent);
+            }
+        }
+    }
+    if shown.is_some() {
+        writeln!(err)?; // leave 100% on its own line
+    }
+    Ok(tally)
+}
+
+// --histogram: one "KIND: count" line per token kind seen anywhere in `input`,
+// most frequent first and ties in name order. Nothing is parsed, so no line fails
+fn print_histogram<R: BufRead, W: Write>(input: R, out: &mut W) -> io::Result<Tally> {
+    let mut tally = Tally::default();
+    let mut counts = HashMap::new();
+    let mut scanner = SCANNER::constructor(String::new());
+    for line in input.lines() {
+        scanner.reset(&line?);
+        let tokens = scanner.tokenize_the_line();
+        if tokens.len() > 1 {
+            tally.lines += 1;
+        }
+        for (kind, n) in token_histogram(&tokens) {
+            *counts.entry(kind).or_insert(0) += n;
+        }
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+    for (kind, n) in counts {
+        writeln!(out, "{}: {}", kind, n)?;
+    }
+    Ok(tally)
+}
+
+pub const PROMPT: &str = "> ";
+
+// interactive loop: prompts on `out`, then handles each line like process_lines
+// but keeps going after a parse error. Ends cleanly at end of input (Ctrl-D).
+pub fn repl<R: BufRead, W: Write, E: Write>(
+    mut input: R,
+    options: &Options,
+    out: &mut W,
+    err: &mut E,
+) -> io::Result<()> {
+    let mut line_number = 0;
+    let mut line = String::new();
+    let mut scanner = SCANNER::constructor(String::new());
+    loop {
+        write!(out, "{}", PROMPT)?;
+        out.flush()?;
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            // finish the prompt line so the shell prompt starts on its own line
+            writeln!(out)?;
+            return Ok(());
+        }
+        line_number += 1;
+        let text = line.trim_end_matches(['\n', '\r']);
+        process_line(&mut scanner, text, line_number, options, out, err)?;
+        err.flush()?;
+    }
+}
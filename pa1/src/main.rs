@@ -1,253 +1,151 @@
-use std::collections::VecDeque;
-use std::process;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, IsTerminal, Write};
+use std::process::ExitCode;
+
+use pal::{Options, Tally, process_lines, repl};
+
+const USAGE: &str = "\
+Usage: ./scanparse [options] [<filename>... | - | -e <expr> | --repl]
+Options:
+  --no-epsilon                            hide EPSILON leaves and empty *DASH nodes
+  --ast                                   print the operator/operand tree, not the parse tree
+  --format=<bfs|indent|sexpr|dot|json>    output style (default: bfs)
+  --indent <n>                            spaces per level for --format=indent (default: 2)
+  --tokens                                print each token with its line:column; don't parse
+  --no-separator                          no blank line after each line's output
+  --no-color                              don't color the --tokens dump, even on a terminal
+  --count                                 print token, node and depth counts per line
+  --histogram                             count each kind of token over the whole input
+  --strict                                fail lines with trailing tokens instead of warning
+  --check                                 print only the lines that fail, with their errors
+  --caret                                 show the failing line with a ^ under the error
+  --json-errors                           report each parse error as a line of JSON
+  --progress                              show the percentage of lines done on stderr
+  --max-depth <n>                         nesting allowed before a line fails (default: 64)
+  --repl                                  prompt for expressions until end of input
+";
 
-// ===== TOKENS & SCANNER =====
-
-#[derive(Debug)]
-enum TOKEN {
-    IDENTIFIER(String),
-    NUMBER(String),
-    PLUS,
-    STAR,
-    BOPEN,
-    BCLOSE,
-    ERROR(()),
-    EOF,
-}
-
-struct SCANNER {
-    index: usize,
-    user_input: Vec<char>,
-}
-
-impl SCANNER {
-    fn constructor(input_string: String) -> Self {
-        SCANNER {
-            index: 0,
-            user_input: input_string.chars().collect(),
-        }
-    }
-
-    fn look_up_current_char(&self) -> Option<char> {
-        self.user_input.get(self.index).copied()
-    }
-
-    fn move_to_next_char(&mut self) -> Option<char> {
-        let c = self.look_up_current_char();
-        if c.is_some() {
-            self.index += 1;
-        }
-        c
-    }
-
-    fn skip_whitespace(&mut self) {
-        while matches!(self.look_up_current_char(), Some(ch) if ch.is_whitespace()) {
-            self.move_to_next_char();
-        }
-    }
-
-    // small helper to accumulate while a predicate holds
-    fn collect_while<F: Fn(char) -> bool>(&mut self, first: char, keep: F) -> String {
-        let mut s = String::from(first);
-        while let Some(next) = self.look_up_current_char() {
-            if keep(next) {
-                // safe unwrap: we just peeked it
-                s.push(self.move_to_next_char().unwrap());
-            } else {
-                break;
-            }
-        }
-        s
-    }
-
-    fn get_next_token(&mut self) -> Option<TOKEN> {
-        self.skip_whitespace();
-        let ch = self.move_to_next_char()?; 
-
-        Some(match ch {
-            '+' => TOKEN::PLUS,
-            '*' => TOKEN::STAR,
-            '(' => TOKEN::BOPEN,
-            ')' => TOKEN::BCLOSE,
-            d if d.is_ascii_digit() => {
-                let num = self.collect_while(d, |c| c.is_ascii_digit());
-                TOKEN::NUMBER(num)
-            }
-            a if a.is_alphabetic() => {
-                let id = self.collect_while(a, |c| c.is_alphabetic());
-                TOKEN::IDENTIFIER(id)
-            }
-            _ => TOKEN::ERROR(()),
-        })
-    }
-
-    fn tokenize_the_line(&mut self) -> Vec<TOKEN> {
-        let mut tokens = Vec::new();
-        while let Some(tok) = self.get_next_token() {
-            tokens.push(tok);
-        }
-        tokens.push(TOKEN::EOF);
-        tokens
-    }
-}
-
-// ===== Minimal tree to control printed layout =====
-
-#[derive(Clone)]
-struct NODE {
-    label: String,
-    children: Vec<NODE>,
-}
-
-impl NODE {
-    fn leaf(label: &str) -> NODE {
-        NODE { label: label.to_string(), children: Vec::new() }
-    }
-    fn with(label: &str, children: Vec<NODE>) -> NODE {
-        NODE { label: label.to_string(), children }
-    }
-}
-
-// ===== PARSER =====
-
-struct PARSER {
-    index: usize,
-    tokens: Vec<TOKEN>,
-}
-
-impl PARSER {
-    fn constructor(tokens: Vec<TOKEN>) -> Self {
-        PARSER { index: 0, tokens }
-    }
-
-    fn current_token(&self) -> &TOKEN {
-        self.tokens.get(self.index).unwrap_or(&TOKEN::EOF)
-    }
-
-    fn move_to_next_token(&mut self) {
-        if self.index < self.tokens.len() {
-            self.index += 1;
-        }
-    }
-
-    // EXPR -> TERM EXPRDASH
-    fn parse_expr(&mut self) -> NODE {
-        let t = self.parse_term();
-        let d = self.parse_exprdash();
-        NODE::with("EXPR", vec![t, d])
-    }
-
-    // EXPRDASH -> + TERM EXPRDASH | ε
-    fn parse_exprdash(&mut self) -> NODE {
-        if let TOKEN::PLUS = self.current_token() {
-            self.move_to_next_token(); // '+'
-            let rhs = self.parse_term();
-            let more = self.parse_exprdash();
-            NODE::with("EXPRDASH", vec![NODE::leaf("PLUS"), rhs, more])
-        } else {
-            NODE::with("EXPRDASH", vec![NODE::leaf("EPSILON")])
-        }
-    }
-
-    // TERM -> FACTOR TERMDASH
-    fn parse_term(&mut self) -> NODE {
-        let f = self.parse_factor();
-        let d = self.parse_termdash();
-        NODE::with("TERM", vec![f, d])
-    }
-
-    // TERMDASH -> * FACTOR TERMDASH | ε
-    fn parse_termdash(&mut self) -> NODE {
-        if let TOKEN::STAR = self.current_token() {
-            self.move_to_next_token(); // '*'
-            let f = self.parse_factor();
-            let more = self.parse_termdash();
-            NODE::with("TERMDASH", vec![NODE::leaf("STAR"), f, more])
-        } else {
-            NODE::with("TERMDASH", vec![NODE::leaf("EPSILON")])
-        }
-    }
+// ===== MAIN FUNCTION =====
 
-    // FACTOR -> IDENTIFIER | NUMBER | ( EXPR )
-    fn parse_factor(&mut self) -> NODE {
-        match self.current_token() {
-            TOKEN::IDENTIFIER(name) => {
-                let leaf = NODE::leaf(&format!("IDENTIFIER({})", name));
-                self.move_to_next_token();
-                NODE::with("FACTOR", vec![leaf])
-            }
-            TOKEN::NUMBER(n) => {
-                let leaf = NODE::leaf(&format!("NUMBER({})", n));
-                self.move_to_next_token();
-                NODE::with("FACTOR", vec![leaf])
-            }
-            TOKEN::BOPEN => {
-                self.move_to_next_token();
-                let inside = self.parse_expr();
-                if let TOKEN::BCLOSE = self.current_token() {
-                    self.move_to_next_token();
-                    NODE::with("FACTOR", vec![NODE::leaf("BOPEN"), inside, NODE::leaf("BCLOSE")])
-                } else {
-                    eprintln!("Error: missing closing parenthesis");
-                    process::exit(1);
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options { color: io::stdout().is_terminal(), ..Options::default() };
+    let mut inline = None;
+    let mut interactive = false;
+    let mut files = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" => match args.next() {
+                Some(expr) => inline = Some(expr),
+                None => {
+                    eprintln!("Error: -e needs an expression");
+                    return ExitCode::FAILURE;
                 }
+            },
+            "--no-epsilon" => options.no_epsilon = true,
+            "--ast" => options.ast = true,
+            "--repl" => interactive = true,
+            "--tokens" => options.tokens = true,
+            "--no-color" => options.color = false,
+            "--no-separator" => options.no_separator = true,
+            "--count" => options.count = true,
+            "--histogram" => options.histogram = true,
+            "--strict" => options.strict = true,
+            "--check" => options.check = true,
+            "--caret" => options.caret = true,
+            "--json-errors" => options.json_errors = true,
+            "--progress" => options.progress = true,
+            // zero or negative widths still indent by one space
+            "--indent" => match args.next().map(|n| n.parse::<i64>()) {
+                Some(Ok(n)) => options.indent = n.max(1) as usize,
+                _ => {
+                    eprintln!("Error: --indent needs a number");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--max-depth" => match args.next().map(|n| n.parse()) {
+                Some(Ok(n)) => options.max_depth = n,
+                _ => {
+                    eprintln!("Error: --max-depth needs a number");
+                    return ExitCode::FAILURE;
+                }
+            },
+            _ if arg.starts_with("--format=") => match arg["--format=".len()..].parse() {
+                Ok(format) => options.format = format,
+                Err(message) => {
+                    eprintln!("Error: {}", message);
+                    return ExitCode::FAILURE;
+                }
+            },
+            // a lone "-" is stdin; anything else starting with '-' is a typo
+            // or a flag we don't have, not a file name
+            _ if arg.starts_with('-') && arg != "-" => {
+                eprint!("Error: unknown option {}\n{}", arg, USAGE);
+                return ExitCode::FAILURE;
             }
-            other => {
-                eprintln!("Error: Unexpected token in FACTOR: {:?}", other);
-                process::exit(1);
-            }
+            _ => files.push(arg),
         }
     }
-}
-
-// ===== Breadth-first printer: one line per level =====
-
-fn bfs_print(root: &NODE) {
-    let mut q: VecDeque<(NODE, usize)> = VecDeque::new();
-    q.push_back((root.clone(), 0));
-    let mut level = 0usize;
-    let mut line: Vec<String> = Vec::new();
-
-    while let Some((node, lv)) = q.pop_front() {
-        if lv != level {
-            println!("{}", line.join(" "));
-            line.clear();
-            level = lv;
+    // -e, --repl and input files are three different ways to feed expressions
+    let sources = inline.is_some() as usize + interactive as usize + !files.is_empty() as usize;
+    if sources > 1 {
+        eprint!("{}", USAGE);
+        return ExitCode::FAILURE;
+    }
+
+    let mut out = io::stdout().lock();
+    let mut err = io::stderr().lock();
+    if interactive {
+        return match repl(io::stdin().lock(), &options, &mut out, &mut err) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(_) => ExitCode::FAILURE,
+        };
+    }
+    if let Some(expr) = inline {
+        return exit_code(process_lines(Cursor::new(expr), &options, &mut out, &mut err), &options);
+    }
+    // no file, or "-", means read expressions from stdin
+    if files.is_empty() || files == ["-"] {
+        return exit_code(
+            process_lines(io::stdin().lock(), &options, &mut out, &mut err),
+            &options,
+        );
+    }
+
+    // with several files, each one's output gets a header and a file that
+    // can't be opened is reported without stopping the rest
+    let mut all_opened = true;
+    let mut tally = Tally::default();
+    for file_name in &files {
+        if files.len() > 1 && writeln!(out, "== {} ==", file_name).is_err() {
+            return ExitCode::FAILURE;
         }
-        line.push(node.label.clone());
-        for child in &node.children {
-            q.push_back((child.clone(), lv + 1));
+        match File::open(file_name) {
+            Ok(file) => match process_lines(BufReader::new(file), &options, &mut out, &mut err) {
+                Ok(file_tally) => tally.add(file_tally),
+                Err(_) => return ExitCode::FAILURE,
+            },
+            Err(e) => {
+                eprintln!("Error: failed to open {}: {}", file_name, e);
+                all_opened = false;
+            }
         }
     }
-
-    if !line.is_empty() {
-        println!("{}", line.join(" "));
-    }
+    let code = exit_code(Ok(tally), &options);
+    if all_opened { code } else { ExitCode::FAILURE }
 }
 
-// ===== MAIN FUNCTION =====
-
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: ./scanparse <filename>");
-        return;
-    }
-
-    let file_name = &args[1];
-    let file_content = std::fs::read_to_string(file_name).expect("Failed to open file");
-
-    for line in file_content.lines() {
-        if line.trim().is_empty() {
-            println!();
-            continue;
+// prints the "N of M lines failed" summary when anything failed, except
+// with --json-errors, where stderr holds nothing but the JSON diagnostics
+fn exit_code(result: io::Result<Tally>, options: &Options) -> ExitCode {
+    match result {
+        Ok(tally) if tally.all_passed() => ExitCode::SUCCESS,
+        Ok(tally) => {
+            if !options.json_errors {
+                eprintln!("{}", tally);
+            }
+            ExitCode::FAILURE
         }
-        let mut scanner = SCANNER::constructor(line.to_string());
-        let tokens = scanner.tokenize_the_line();
-        let mut parser = PARSER::constructor(tokens);
-
-        let tree = parser.parse_expr();
-        bfs_print(&tree);
-        println!();
+        Err(_) => ExitCode::FAILURE,
     }
 }
@@ -0,0 +1,165 @@
+use pal::{
+    Ast, AstError, EvalError, NODE, PARSER, SCANNER, ast_to_string, eval, fold_constants, parse,
+    parse_recovering, to_ast, to_infix, tokenize,
+};
+
+fn ast_of(input: &str) -> Ast {
+    to_ast(&parse(tokenize(input).unwrap()).unwrap()).unwrap()
+}
+
+fn num(n: f64) -> Box<Ast> {
+    Box::new(Ast::Num(n))
+}
+
+#[test]
+fn addition_chain_is_left_associated() {
+    let expected = Ast::Add(Box::new(Ast::Add(num(1.0), num(2.0))), num(3.0));
+    assert_eq!(ast_of("1+2+3"), expected);
+}
+
+#[test]
+fn subtraction_chain_is_left_associated() {
+    let left = Ast::Sub(Box::new(Ast::Sub(num(10.0), num(3.0))), num(2.0));
+    assert_eq!(ast_of("10 - 3 - 2"), left);
+    let grouped = Ast::Sub(num(10.0), Box::new(Ast::Sub(num(3.0), num(2.0))));
+    assert_eq!(ast_of("10 - (3 - 2)"), grouped);
+}
+
+#[test]
+fn precedence_and_grouping_survive() {
+    let var = |name: &str| Box::new(Ast::Var(name.into()));
+    assert_eq!(ast_of("a + b * 2"), Ast::Add(var("a"), Box::new(Ast::Mul(var("b"), num(2.0)))));
+    assert_eq!(ast_of("(a + b) * 2"), Ast::Mul(Box::new(Ast::Add(var("a"), var("b"))), num(2.0)));
+}
+
+#[test]
+fn power_and_negation_nodes() {
+    let pow = Ast::Pow(num(2.0), Box::new(Ast::Pow(num(3.0), num(2.0))));
+    assert_eq!(ast_of("2 ^ 3 ^ 2"), pow);
+    let neg_x = Box::new(Ast::Neg(Box::new(Ast::Var("x".into()))));
+    assert_eq!(ast_of("-x - 1"), Ast::Sub(neg_x, num(1.0)));
+}
+
+#[test]
+fn all_numeric_trees_fold_to_one_number() {
+    let ast = Ast::Add(num(1.0), Box::new(Ast::Mul(num(2.0), num(3.0))));
+    assert_eq!(fold_constants(ast), Ast::Num(7.0));
+    assert_eq!(fold_constants(ast_of("-(2 ^ 3) % 5 < 0")), Ast::Num(1.0));
+}
+
+#[test]
+fn folding_is_f64_arithmetic_where_eval_would_overflow() {
+    let input = "2 ^ 63";
+    assert_eq!(fold_constants(ast_of(input)), Ast::Num(9_223_372_036_854_775_808.0));
+    assert_eq!(eval(&parse(tokenize(input).unwrap()).unwrap()), Err(EvalError::Overflow));
+}
+
+#[test]
+fn mixed_trees_fold_only_their_numeric_parts() {
+    let x = || Box::new(Ast::Var("x".into()));
+    assert_eq!(fold_constants(ast_of("x * (2 + 3)")), Ast::Mul(x(), num(5.0)));
+    // identities collapse once their constant side has folded
+    assert_eq!(fold_constants(ast_of("x + 0")), Ast::Var("x".into()));
+    assert_eq!(fold_constants(ast_of("(3 - 2) * x ^ (4 - 3)")), Ast::Var("x".into()));
+    // x * 0 is kept, since x might be NaN
+    assert_eq!(fold_constants(ast_of("x * 0")), Ast::Mul(x(), num(0.0)));
+}
+
+fn var(name: &str) -> Box<Ast> {
+    Box::new(Ast::Var(name.to_string()))
+}
+
+#[test]
+fn printing_adds_parentheses_only_for_lower_precedence_operands() {
+    let sum = || Box::new(Ast::Add(num(1.0), num(2.0)));
+    assert_eq!(ast_to_string(&Ast::Mul(sum(), num(3.0))), "(1 + 2) * 3");
+    let product = Box::new(Ast::Mul(num(2.0), num(3.0)));
+    assert_eq!(ast_to_string(&Ast::Add(num(1.0), product)), "1 + 2 * 3");
+    let negated = Ast::Neg(Box::new(Ast::Pow(var("a"), num(2.0))));
+    assert_eq!(ast_to_string(&negated), "-a ^ 2");
+    let base = Ast::Pow(Box::new(Ast::Neg(var("a"))), num(2.0));
+    assert_eq!(ast_to_string(&base), "(-a) ^ 2");
+}
+
+#[test]
+fn printing_follows_associativity() {
+    // - and / associate left, so only a right-nested one needs parentheses
+    let left = Ast::Sub(Box::new(Ast::Sub(var("a"), var("b"))), var("c"));
+    assert_eq!(ast_to_string(&left), "a - b - c");
+    let right = Ast::Sub(var("a"), Box::new(Ast::Sub(var("b"), var("c"))));
+    assert_eq!(ast_to_string(&right), "a - (b - c)");
+    let div = Ast::Div(var("a"), Box::new(Ast::Mul(var("b"), var("c"))));
+    assert_eq!(ast_to_string(&div), "a / (b * c)");
+    // ^ associates right, so it is the other way around
+    let right = Ast::Pow(var("a"), Box::new(Ast::Pow(var("b"), var("c"))));
+    assert_eq!(ast_to_string(&right), "a ^ b ^ c");
+    let left = Ast::Pow(Box::new(Ast::Pow(var("a"), var("b"))), var("c"));
+    assert_eq!(ast_to_string(&left), "(a ^ b) ^ c");
+}
+
+#[test]
+fn factorial_prints_postfix_with_parens_only_when_needed() {
+    let cases =
+        [("5!", "5!"), ("(1+2)!", "(1 + 2)!"), ("-3!!", "-3!!"), ("f(x)! ^ 2", "f(x)! ^ 2")];
+    for (input, printed) in cases {
+        assert_eq!(ast_to_string(&ast_of(input)), printed);
+    }
+    assert_eq!(ast_of("2!"), Ast::Factorial(num(2.0)));
+    assert_eq!(fold_constants(ast_of("(1+2)! - 1")), Ast::Num(5.0));
+    assert_eq!(ast_to_string(&fold_constants(ast_of("(0-3)!"))), "(-3)!");
+}
+
+fn pratt_of(input: &str) -> Result<Ast, String> {
+    let (tokens, positions) = SCANNER::constructor(input.to_string()).tokenize_with_positions();
+    let mut parser = PARSER::constructor(tokens).with_positions(positions).pratt(true);
+    parser.parse_ast().map_err(|e| e.to_string())
+}
+
+#[test]
+fn pratt_backend_builds_the_same_ast_as_the_recursive_parser() {
+    let inputs = [
+        "1 + 2 * 3",
+        "1 - 2 - 3",
+        "100 / 10 / 5 % 3",
+        "2 ^ 3 ^ 2",
+        "2 ** 3 ^ 2",
+        "-2 ^ 2",
+        "-x * -y - --z",
+        "(1 + 2) * (3 - 4) / 5",
+        "a + b * c ^ d - e",
+        "((7))",
+        "0xFF + 1_000 * 1.5e1",
+    ];
+    for input in inputs {
+        assert_eq!(pratt_of(input), Ok(ast_of(input)), "{}", input);
+    }
+}
+
+#[test]
+fn pratt_backend_reports_errors_like_the_recursive_parser() {
+    assert_eq!(pratt_of("1 + * 2").unwrap_err(), "expected a factor, found '*'");
+    assert_eq!(pratt_of("2 ^ -3").unwrap_err(), "expected a factor, found '-'");
+    assert_eq!(pratt_of("(1 + 2").unwrap_err(), "unclosed '(' opened at line 1, column 1");
+    // what it does not cover ends the expression
+    assert_eq!(pratt_of("1 < 2").unwrap_err(), "unexpected trailing token '<'");
+    // and the default backend for parse_ast is still the recursive grammar
+    let mut parser = PARSER::constructor(tokenize("1 < 2").unwrap());
+    assert!(matches!(parser.parse_ast(), Ok(Ast::Lt(..))));
+}
+
+#[test]
+fn trees_to_ast_cannot_convert_are_errors_not_panics() {
+    // parse_recovering stands an ERROR leaf in for the missing factor
+    let (tree, errors) = parse_recovering(tokenize("1 + * 2").unwrap());
+    assert_eq!(errors.len(), 1);
+    assert_eq!(to_ast(&tree), Err(AstError::ErrorLeaf));
+    assert_eq!(to_infix(&tree), Err(AstError::ErrorLeaf));
+    // a clean recovery still converts
+    let (tree, errors) = parse_recovering(tokenize("1 + 2").unwrap());
+    assert!(errors.is_empty());
+    assert_eq!(to_infix(&tree).unwrap(), "1 + 2");
+
+    let err = to_ast(&NODE::with("EXPR", vec![NODE::leaf("BOGUS")])).unwrap_err();
+    assert_eq!(err, AstError::Malformed("EXPR".into()));
+    assert_eq!(err.to_string(), "EXPR node is not shaped like PARSER output");
+}
@@ -0,0 +1,159 @@
+use std::process::{Command, Output};
+
+fn scanparse(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pal")).args(args).output().unwrap()
+}
+
+#[test]
+fn inline_expression_is_parsed() {
+    let output = scanparse(&["--no-epsilon", "-e", "1 + 2 * 3"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().next(), Some("EXPR"));
+    assert!(stdout.contains("NUMBER(1)"));
+    assert!(stdout.contains("NUMBER(3)"));
+}
+
+#[test]
+fn dash_e_without_an_expression_fails() {
+    let output = scanparse(&["-e"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Error: -e needs an expression\n");
+}
+
+#[test]
+fn unknown_options_fail_with_usage_instead_of_being_opened() {
+    for flag in ["--help", "--cheks", "-x"] {
+        let output = scanparse(&[flag, "-e", "1"]);
+        assert!(!output.status.success(), "{}", flag);
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.starts_with(&format!("Error: unknown option {}\nUsage: ", flag)));
+    }
+}
+
+#[test]
+fn conflicting_input_sources_fail_with_usage() {
+    let output = scanparse(&["-e", "1", "--repl"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with("Usage: "));
+}
+
+#[test]
+fn lone_dash_still_reads_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pal"))
+        .args(["--no-epsilon", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"7\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let tree = "EXPR\nTERM\nPOWER\nFACTOR\nNUMBER(7)\n\n";
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), tree);
+}
+
+#[test]
+fn missing_file_is_reported_and_the_next_file_still_runs() {
+    let dir = std::env::temp_dir();
+    let present = dir.join(format!("scanparse-present-{}.txt", std::process::id()));
+    let missing = dir.join(format!("scanparse-missing-{}.txt", std::process::id()));
+    std::fs::write(&present, "x\n").unwrap();
+
+    let output = scanparse(&[
+        "--no-epsilon",
+        missing.to_str().unwrap(),
+        present.to_str().unwrap(),
+    ]);
+    std::fs::remove_file(&present).unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected = format!(
+        "== {} ==\n== {} ==\nEXPR\nTERM\nPOWER\nFACTOR\nIDENTIFIER(x)\n\n",
+        missing.display(),
+        present.display()
+    );
+    assert_eq!(stdout, expected);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with(&format!("Error: failed to open {}", missing.display())));
+}
+
+#[test]
+fn failed_lines_are_summed_across_files() {
+    let dir = std::env::temp_dir();
+    let first = dir.join(format!("scanparse-mixed-a-{}.txt", std::process::id()));
+    let second = dir.join(format!("scanparse-mixed-b-{}.txt", std::process::id()));
+    std::fs::write(&first, "1\n(2\n").unwrap();
+    std::fs::write(&second, "3 +\n4\n5\n").unwrap();
+
+    let output = scanparse(&[first.to_str().unwrap(), second.to_str().unwrap()]);
+    std::fs::remove_file(&first).unwrap();
+    std::fs::remove_file(&second).unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 3);
+    assert_eq!(stderr.lines().last(), Some("2 of 5 lines failed"));
+}
+
+#[test]
+fn piped_token_dump_has_no_color() {
+    // stdout is a pipe here, so color is off with or without --no-color
+    for args in [&["--tokens", "-e", "a + 1"][..], &["--tokens", "--no-color", "-e", "a + 1"]] {
+        let output = scanparse(args);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "1:1 a\n1:3 +\n1:5 1\n1:6 <eof>\n\n");
+    }
+}
+
+#[test]
+fn no_separator_drops_the_blank_line_after_each_tree() {
+    let tree = "EXPR\nTERM\nPOWER\nFACTOR\nNUMBER(7)\n";
+    let output = scanparse(&["--no-epsilon", "-e", "7"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\n", tree));
+    let output = scanparse(&["--no-epsilon", "--no-separator", "-e", "7"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), tree);
+}
+
+#[test]
+fn check_is_silent_for_valid_input_and_lists_failing_lines() {
+    let dir = std::env::temp_dir();
+    let valid = dir.join(format!("scanparse-check-ok-{}.txt", std::process::id()));
+    let invalid = dir.join(format!("scanparse-check-bad-{}.txt", std::process::id()));
+    std::fs::write(&valid, "1 + 2\n\nf(x) * 3\n").unwrap();
+    std::fs::write(&invalid, "1 + 2\n(3 * 4\n5\n").unwrap();
+
+    let passed = scanparse(&["--check", valid.to_str().unwrap()]);
+    let failed = scanparse(&["--check", invalid.to_str().unwrap()]);
+    std::fs::remove_file(&valid).unwrap();
+    std::fs::remove_file(&invalid).unwrap();
+
+    assert!(passed.status.success());
+    assert!(passed.stdout.is_empty() && passed.stderr.is_empty());
+
+    assert_eq!(failed.status.code(), Some(1));
+    assert!(failed.stdout.is_empty());
+    let stderr = String::from_utf8(failed.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "2: (3 * 4");
+    assert!(lines[1].starts_with("Error at line 2, column 7: "));
+    assert_eq!(lines[2], "1 of 3 lines failed");
+}
+
+#[test]
+fn json_errors_print_one_parsable_object_per_failing_line() {
+    let output = scanparse(&["--json-errors", "-e", "(1 + 2"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 1);
+    let error: serde_json::Value = serde_json::from_str(stderr.trim_end()).unwrap();
+    assert_eq!(error["line"], 1);
+    assert_eq!(error["col"], 7);
+    assert_eq!(error["message"], "unclosed '(' opened at line 1, column 1");
+}
@@ -0,0 +1,188 @@
+use std::io::Cursor;
+
+use pal::{
+    Format, Options, PROMPT, Tally, bfs_print, parse, percent_done, print_tree_indented,
+    process_lines, repl, to_dot, to_json, to_sexpr, tokenize,
+};
+
+// runs process_lines over `input`, returning (tally, stdout, stderr)
+fn run_tally(input: &str, options: &Options) -> (Tally, String, String) {
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    let tally = process_lines(Cursor::new(input), options, &mut out, &mut err).unwrap();
+    (tally, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+}
+
+// like run_tally, but only says whether every line parsed
+fn run(input: &str, options: &Options) -> (bool, String, String) {
+    let (tally, out, err) = run_tally(input, options);
+    (tally.all_passed(), out, err)
+}
+
+#[test]
+fn reader_input_is_parsed_line_by_line() {
+    let options = Options { no_epsilon: true, ..Options::default() };
+    let (ok, out, err) = run("x\n\n7\n", &options);
+    assert!(ok);
+    let x = "EXPR\nTERM\nPOWER\nFACTOR\nIDENTIFIER(x)\n";
+    let seven = "EXPR\nTERM\nPOWER\nFACTOR\nNUMBER(7)\n";
+    assert_eq!(out, format!("{}\n\n{}\n", x, seven));
+    assert_eq!(err, "");
+}
+
+#[test]
+fn bad_lines_are_reported_and_the_run_continues() {
+    let options = Options { no_epsilon: true, ..Options::default() };
+    let (tally, out, err) = run_tally("(1\n2\n\n1 +\n@\n", &options);
+    assert_eq!(tally, Tally { lines: 4, failed: 3 });
+    assert_eq!(tally.to_string(), "3 of 4 lines failed");
+    assert_eq!(out, "EXPR\nTERM\nPOWER\nFACTOR\nNUMBER(2)\n\n\n");
+    let reported: Vec<&str> = err.lines().collect();
+    assert_eq!(
+        reported,
+        [
+            "Error at line 1, column 3: unclosed '(' opened at line 1, column 1",
+            "Error at line 4, column 4: expected a factor, found '<eof>'",
+            "Error at line 5, column 1: invalid character '@'",
+        ]
+    );
+}
+
+#[test]
+fn trailing_tokens_warn_by_default_and_fail_under_strict() {
+    let lenient = Options { no_epsilon: true, ..Options::default() };
+    let (ok, out, err) = run("1 2\n", &lenient);
+    assert!(ok);
+    assert_eq!(out, "EXPR\nTERM\nPOWER\nFACTOR\nNUMBER(1)\n\n");
+    assert_eq!(err, "Warning at line 1, column 3: ignoring unexpected trailing token '2'\n");
+
+    let strict = Options { strict: true, ..lenient };
+    let (tally, out, err) = run_tally("1 2\n1 @\n3\n", &strict);
+    assert_eq!(tally, Tally { lines: 3, failed: 2 });
+    assert_eq!(out, "EXPR\nTERM\nPOWER\nFACTOR\nNUMBER(3)\n\n");
+    let reported: Vec<&str> = err.lines().collect();
+    assert_eq!(
+        reported,
+        [
+            "Error at line 1, column 3: unexpected trailing token '2'",
+            "Error at line 2, column 3: invalid character '@'",
+        ]
+    );
+}
+
+#[test]
+fn each_format_name_dispatches_to_its_printer() {
+    let tree = parse(tokenize("a*2").unwrap()).unwrap();
+    let mut bfs = Vec::new();
+    bfs_print(&tree, &mut bfs).unwrap();
+    let mut indent = Vec::new();
+    print_tree_indented(&tree, &mut indent).unwrap();
+    let cases = [
+        ("bfs", String::from_utf8(bfs).unwrap()),
+        ("indent", String::from_utf8(indent).unwrap()),
+        ("sexpr", to_sexpr(&tree) + "\n"),
+        ("dot", to_dot(&tree)),
+        ("json", to_json(&tree) + "\n"),
+    ];
+    for (name, printed) in cases {
+        let format: Format = name.parse().unwrap();
+        let (ok, out, _) = run("a*2", &Options { format, ..Options::default() });
+        assert!(ok);
+        assert_eq!(out, printed + "\n", "format {}", name);
+    }
+}
+
+#[test]
+fn unknown_format_lists_the_choices() {
+    let err = "tree".parse::<Format>().unwrap_err();
+    assert_eq!(err, "unknown format 'tree' (expected one of: bfs, indent, sexpr, dot, json)");
+}
+
+#[test]
+fn lines_without_tokens_print_blank_and_are_not_parsed() {
+    let options = Options { no_epsilon: true, ..Options::default() };
+    let (ok, out, err) = run("   \n// just a note\n1\n", &options);
+    assert!(ok, "stderr: {}", err);
+    assert_eq!(out, "\n\nEXPR\nTERM\nPOWER\nFACTOR\nNUMBER(1)\n\n");
+}
+
+#[test]
+fn repl_keeps_going_after_a_bad_line() {
+    let options = Options { no_epsilon: true, ..Options::default() };
+    let mut out = Vec::new();
+    let mut err = Vec::new();
+    repl(Cursor::new("7\n(1\n"), &options, &mut out, &mut err).unwrap();
+    let seven = "EXPR\nTERM\nPOWER\nFACTOR\nNUMBER(7)\n";
+    let out = String::from_utf8(out).unwrap();
+    // a prompt before each line, and one more that meets end of input
+    assert_eq!(out, format!("{p}{}\n{p}{p}\n", seven, p = PROMPT));
+    let err = String::from_utf8(err).unwrap();
+    assert_eq!(err, "Error at line 2, column 3: unclosed '(' opened at line 2, column 1\n");
+}
+
+#[test]
+fn tokens_mode_lists_each_token_with_its_position() {
+    let options = Options { tokens: true, ..Options::default() };
+    let (ok, out, _) = run("1 + x\n", &options);
+    assert!(ok);
+    assert_eq!(out, "1:1 1\n1:3 +\n1:5 x\n1:6 <eof>\n\n");
+}
+
+#[test]
+fn token_colors_are_ansi_escapes_only_when_enabled() {
+    let plain = Options { tokens: true, ..Options::default() };
+    let (_, out, _) = run("x1 + 2 @\n", &plain);
+    assert!(!out.contains('\x1b'));
+
+    let colored = Options { color: true, ..plain };
+    let (_, out, _) = run("x1 + 2 @\n", &colored);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "1:1 \x1b[36mx1\x1b[0m",
+            "1:4 \x1b[35m+\x1b[0m",
+            "1:6 \x1b[33m2\x1b[0m",
+            "1:8 \x1b[31m@\x1b[0m",
+            "1:9 <eof>",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn blank_separator_lines_can_be_left_out() {
+    let one = "(EXPR (TERM (POWER (FACTOR NUMBER(1)))))\n";
+    let two = "(EXPR (TERM (POWER (FACTOR NUMBER(2)))))\n";
+    let with = Options { format: Format::Sexpr, no_epsilon: true, ..Options::default() };
+    assert_eq!(run("1\n\n2\n", &with).1, format!("{}\n\n{}\n", one, two));
+
+    let without = Options { no_separator: true, ..with };
+    assert_eq!(run("1\n\n2\n", &without).1, format!("{}{}", one, two));
+    let tokens = Options { tokens: true, ..without };
+    assert_eq!(run("x\n", &tokens).1, "1:1 x\n1:2 <eof>\n");
+}
+
+#[test]
+fn a_line_of_only_operators_fails_alone() {
+    let options = Options { no_epsilon: true, ..Options::default() };
+    let (tally, out, err) = run_tally("+ + +\n1+2\n", &options);
+    assert_eq!(tally, Tally { lines: 2, failed: 1 });
+    assert_eq!(err, "Error at line 1, column 1: expected a factor, found '+'\n");
+    assert!(out.starts_with("EXPR\nTERM EXPRDASH\n"));
+}
+
+#[test]
+fn progress_is_the_share_of_lines_done() {
+    assert_eq!(percent_done(50, 100), 50);
+    assert_eq!(percent_done(3, 6), 50);
+    assert_eq!(percent_done(1, 3), 33);
+    assert_eq!((percent_done(0, 0), percent_done(9, 4)), (100, 100));
+
+    // every whole percent that changes is drawn once, then the line is ended
+    let options = Options { progress: true, check: true, ..Options::default() };
+    let (tally, out, err) = run_tally("1\n2\n3 +\n4\n", &options);
+    assert_eq!((tally.lines, tally.failed, out.as_str()), (4, 1, ""));
+    let error = "3: 3 +\nError at line 3, column 4: expected a factor, found '<eof>'\n";
+    assert_eq!(err, format!("25%\r50%\r{}75%\r100%\r\n", error));
+}
@@ -0,0 +1,125 @@
+use std::collections::{BTreeSet, HashMap};
+
+use pal::{EvalError, Value, eval, eval_value, eval_with, free_variables, parse, tokenize};
+
+fn eval_str(input: &str) -> Result<f64, EvalError> {
+    eval(&parse(tokenize(input).unwrap()).unwrap())
+}
+
+#[test]
+fn star_binds_tighter_than_plus() {
+    assert_eq!(eval_str("1+2*3"), Ok(7.0));
+}
+
+#[test]
+fn parentheses_group_first() {
+    assert_eq!(eval_str("(1+2)*3"), Ok(9.0));
+}
+
+#[test]
+fn square_brackets_and_braces_group_first_too() {
+    assert_eq!(eval_str("[1+2]*3"), Ok(9.0));
+    assert_eq!(eval_str("{2 * [1 + (3)]} ^ 2"), Ok(64.0));
+}
+
+#[test]
+fn subtraction_and_division_fold_left_across_the_chain() {
+    // EXPRDASH nests to the right, but the operators still apply left to right
+    assert_eq!(eval_str("10 - 3 - 2"), Ok(5.0));
+    assert_eq!(eval_str("10 - (3 - 2)"), Ok(9.0));
+    assert_eq!(eval_str("1 - 2 + 3"), Ok(2.0));
+    assert_eq!(eval_str("100 / 10 / 5"), Ok(2.0));
+    assert_eq!(eval_str("100 / (10 / 5)"), Ok(50.0));
+}
+
+#[test]
+fn every_operator_and_literal_form() {
+    assert_eq!(eval_str("2 ^ 3 ^ 2"), Ok(512.0));
+    assert_eq!(eval_str("2 ** 3 ** 2"), Ok(512.0));
+    assert_eq!(eval_str("-2 ** 2"), Ok(-4.0));
+    assert_eq!(eval_str("-2 ^ 2"), Ok(-4.0));
+    assert_eq!(eval_str("7 / 2 - 1"), Ok(2.5));
+    assert_eq!(eval_str("7 % 3 * 2"), Ok(2.0));
+    assert_eq!(eval_str("0xFF + 0b10 + 1_000 + 1.5e1"), Ok(1272.0));
+    assert_eq!(eval_str("1 + 1 <= 2"), Ok(1.0));
+    assert_eq!(eval_str("3 > 4"), Ok(0.0));
+    assert_eq!(eval_str("2 * 3 == 6"), Ok(1.0));
+    assert_eq!(eval_str("1 != 1"), Ok(0.0));
+    assert_eq!(eval_str("1; 2 + 3;"), Ok(5.0));
+    assert_eq!(eval_str("1, 2 * 3"), Ok(6.0));
+    assert_eq!(eval_str("2 && 0 || 3 > 1"), Ok(1.0));
+    // the right side is skipped once the left decides
+    assert_eq!(eval_str("0 && unknown"), Ok(0.0));
+    assert_eq!(eval_str("!0 + !5"), Ok(1.0));
+    assert_eq!(eval_str("2 > 1 ? 10 : unknown"), Ok(10.0));
+}
+
+#[test]
+fn unknown_identifier_is_an_error() {
+    assert_eq!(eval_str("x + 1"), Err(EvalError::UnknownVariable("x".into())));
+}
+
+#[test]
+fn identifiers_read_from_the_environment() {
+    let tree = parse(tokenize("x * 2").unwrap()).unwrap();
+    let env = HashMap::from([("x".to_string(), 5.0)]);
+    assert_eq!(eval_with(&tree, &env), Ok(10.0));
+}
+
+#[test]
+fn missing_binding_is_still_unknown() {
+    let tree = parse(tokenize("x * y").unwrap()).unwrap();
+    let env = HashMap::from([("x".to_string(), 5.0)]);
+    assert_eq!(eval_with(&tree, &env), Err(EvalError::UnknownVariable("y".into())));
+}
+
+#[test]
+fn integer_literals_stay_exact() {
+    let exact = |input: &str| eval_value(&parse(tokenize(input).unwrap()).unwrap());
+    assert_eq!(exact("9007199254740993"), Ok(Value::Int(9_007_199_254_740_993)));
+    assert_eq!(exact("9007199254740992 + 1"), Ok(Value::Int(9_007_199_254_740_993)));
+    assert_eq!(exact("6 / 3"), Ok(Value::Int(2)));
+    // real literals and uneven division fall back to f64
+    assert_eq!(exact("7 / 2"), Ok(Value::Float(3.5)));
+    assert_eq!(exact("1.0 + 1"), Ok(Value::Float(2.0)));
+}
+
+#[test]
+fn integer_overflow_is_an_error() {
+    assert_eq!(eval_str("9223372036854775807 + 1"), Err(EvalError::Overflow));
+    assert_eq!(eval_str("21!"), Err(EvalError::Overflow));
+    assert_eq!(eval_str("2 ^ 63"), Err(EvalError::Overflow));
+    assert_eq!(eval_str("9223372036854775808"), Err(EvalError::Overflow));
+    assert_eq!(eval_str("2.0 ^ 63"), Ok(9_223_372_036_854_775_808.0));
+}
+
+#[test]
+fn free_variables_are_deduped_and_sorted() {
+    let names = |input: &str| free_variables(&parse(tokenize(input).unwrap()).unwrap());
+    let expected: BTreeSet<String> = ["a".to_string(), "b".to_string()].into();
+    assert_eq!(names("a + b * a"), expected);
+    assert_eq!(names("zeta - alpha").into_iter().collect::<Vec<_>>(), ["alpha", "zeta"]);
+    assert!(names("1 + 2").is_empty());
+    // targets and callees are not reads; their arguments and values are
+    assert_eq!(names("x = f(y, 2) + g(x)").into_iter().collect::<Vec<_>>(), ["x", "y"]);
+
+    // binding the free variables is enough to evaluate
+    let tree = parse(tokenize("a + b * a").unwrap()).unwrap();
+    let env: HashMap<String, f64> = free_variables(&tree).into_iter().map(|n| (n, 2.0)).collect();
+    assert_eq!(eval_with(&tree, &env), Ok(6.0));
+}
+
+#[test]
+fn factorial_is_exact_for_integers() {
+    assert_eq!(eval_value(&parse(tokenize("5!").unwrap()).unwrap()), Ok(Value::Int(120)));
+    assert_eq!(eval_str("0! + (1+2)! * 2"), Ok(13.0));
+    // postfix binds tighter than ^ and prefix -
+    assert_eq!(eval_str("3!^2"), Ok(36.0));
+    assert_eq!(eval_str("2^3!"), Ok(64.0));
+    assert_eq!(eval_str("-3!!"), Ok(-720.0));
+    assert_eq!(eval_str("4.0!"), Ok(24.0));
+    let err = eval_str("(0-3)!").unwrap_err();
+    assert_eq!(err, EvalError::InvalidFactorial("-3".into()));
+    assert_eq!(err.to_string(), "factorial of -3 (it needs a whole number >= 0)");
+    assert!(eval_str("1.5!").is_err());
+}
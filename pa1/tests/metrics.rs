@@ -0,0 +1,59 @@
+use pal::{NODE, count_nodes, max_width, parse, prune_epsilon, tokenize, tree_depth};
+
+fn tree_of(input: &str) -> NODE {
+    parse(tokenize(input).unwrap()).unwrap()
+}
+
+#[test]
+fn counts_and_depth_of_one_plus_two() {
+    let tree = tree_of("1+2");
+    // EXPR, then 8 nodes for each TERM subtree, plus EXPRDASH, PLUS, EXPRDASH and EPSILON
+    assert_eq!(count_nodes(&tree), 21);
+    // EXPR -> EXPRDASH -> TERM -> POWER -> FACTOR -> NUMBER(2)
+    assert_eq!(tree_depth(&tree), 5);
+
+    let pruned = prune_epsilon(&tree);
+    assert_eq!(count_nodes(&pruned), 11);
+    assert_eq!(tree_depth(&pruned), 5);
+}
+
+#[test]
+fn a_leaf_is_one_node_at_depth_zero() {
+    let leaf = NODE::leaf("EPSILON");
+    assert_eq!((count_nodes(&leaf), tree_depth(&leaf)), (1, 0));
+}
+
+#[test]
+fn depth_and_width_of_hand_built_trees() {
+    //     A
+    //   / | \
+    //  B  C  D
+    //  |     |
+    //  E     F
+    //        |
+    //        G
+    let tree = NODE::with(
+        "A",
+        vec![
+            NODE::with("B", vec![NODE::leaf("E")]),
+            NODE::leaf("C"),
+            NODE::with("D", vec![NODE::with("F", vec![NODE::leaf("G")])]),
+        ],
+    );
+    assert_eq!(tree_depth(&tree), 3);
+    assert_eq!(max_width(&tree), 3);
+
+    // a chain is deep but one wide
+    let chain = NODE::with("A", vec![NODE::with("B", vec![NODE::leaf("C")])]);
+    assert_eq!((tree_depth(&chain), max_width(&chain)), (2, 1));
+    assert_eq!(max_width(&NODE::leaf("A")), 1);
+}
+
+#[test]
+fn width_of_one_plus_two() {
+    let tree = tree_of("1+2");
+    // FACTOR POWERDASH EPSILON POWER TERMDASH EPSILON, three levels down
+    assert_eq!(max_width(&tree), 6);
+    // pruned, the widest level is POWER PLUS TERM
+    assert_eq!(max_width(&prune_epsilon(&tree)), 3);
+}
@@ -0,0 +1,634 @@
+use pal::{
+    DEFAULT_MAX_DEPTH, NODE, PARSER, ParseError, SCANNER, TOKEN, eval, parse, parse_recovering,
+    parse_str, render_error, to_infix, tokenize,
+};
+
+// parses with token positions attached, as the CLI does
+fn parse_positioned(input: &str) -> Result<NODE, ParseError> {
+    let (tokens, positions) = SCANNER::constructor(input.to_string()).tokenize_with_positions();
+    PARSER::constructor(tokens).with_positions(positions).parse()
+}
+
+fn labels(nodes: &[NODE]) -> Vec<&str> {
+    nodes.iter().map(|n| n.label.as_str()).collect()
+}
+
+#[test]
+fn parses_a_string_end_to_end() {
+    let tree = parse(tokenize("1 + x").unwrap()).unwrap();
+    assert_eq!(tree.label, "EXPR");
+    assert_eq!(labels(&tree.children), ["TERM", "EXPRDASH"]);
+
+    let exprdash = &tree.children[1];
+    assert_eq!(labels(&exprdash.children), ["PLUS", "TERM", "EXPRDASH"]);
+    let factor = &exprdash.children[1].children[0].children[0];
+    assert_eq!(labels(&factor.children), ["IDENTIFIER(x)"]);
+}
+
+#[test]
+fn missing_closing_paren_is_an_error() {
+    let err = parse(tokenize("(1").unwrap()).unwrap_err();
+    assert!(matches!(err, ParseError::MissingClosingParen { .. }));
+    assert_eq!(err.to_string(), "missing closing parenthesis");
+}
+
+#[test]
+fn errors_show_the_offending_token_text() {
+    let err = parse(tokenize("1 + + 2").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "expected a factor, found '+'");
+}
+
+// walks TERM -> POWER for an expression with a single term
+fn only_power(tree: &NODE) -> &NODE {
+    &tree.children[0].children[0]
+}
+
+#[test]
+fn caret_is_right_associative() {
+    let tree = parse(tokenize("2 ^ 3 ^ 2").unwrap()).unwrap();
+    let outer = only_power(&tree);
+    assert_eq!(labels(&outer.children), ["FACTOR", "POWERDASH"]);
+    assert_eq!(outer.children[0].children[0].label, "NUMBER(2)");
+
+    // the right operand of the first ^ is itself a POWER holding 3 ^ 2
+    let inner = &outer.children[1].children[1];
+    assert_eq!(inner.label, "POWER");
+    assert_eq!(inner.children[0].children[0].label, "NUMBER(3)");
+    let innermost = &inner.children[1].children[1];
+    assert_eq!(innermost.children[0].children[0].label, "NUMBER(2)");
+    assert_eq!(innermost.children[1].children[0].label, "EPSILON");
+}
+
+#[test]
+fn double_star_shares_the_power_grammar_with_caret() {
+    let tree = parse(tokenize("2 ** 3 ^ 2").unwrap()).unwrap();
+    let outer = only_power(&tree);
+    assert_eq!(labels(&outer.children[1].children), ["POW", "POWER"]);
+    let inner = &outer.children[1].children[1];
+    assert_eq!(labels(&inner.children[1].children), ["CARET", "POWER"]);
+    // `* *` is two multiplications, so the second star has no left operand
+    let err = parse(tokenize("2 * * 3").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "expected a factor, found '*'");
+}
+
+#[test]
+fn caret_binds_tighter_than_star() {
+    let tree = parse(tokenize("2 ^ 3 * 4").unwrap()).unwrap();
+    let term = &tree.children[0];
+    assert_eq!(labels(&term.children[0].children[1].children), ["CARET", "POWER"]);
+    assert_eq!(labels(&term.children[1].children), ["STAR", "POWER", "TERMDASH"]);
+}
+
+#[test]
+fn leading_minus_negates_a_number() {
+    let tree = parse(tokenize("-5").unwrap()).unwrap();
+    let neg = &tree.children[0].children[0];
+    assert_eq!(neg.label, "NEG");
+    assert_eq!(labels(&neg.children), ["MINUS", "POWER"]);
+    assert_eq!(neg.children[1].children[0].children[0].label, "NUMBER(5)");
+}
+
+#[test]
+fn minus_negates_a_parenthesized_expression() {
+    let tree = parse(tokenize("-(1+2)").unwrap()).unwrap();
+    let neg = &tree.children[0].children[0];
+    let factor = &neg.children[1].children[0];
+    assert_eq!(labels(&factor.children), ["BOPEN", "EXPR", "BCLOSE"]);
+}
+
+#[test]
+fn negations_stack_and_follow_operators() {
+    let tree = parse(tokenize("--3").unwrap()).unwrap();
+    let outer = &tree.children[0].children[0];
+    assert_eq!(labels(&outer.children), ["MINUS", "NEG"]);
+
+    let tree = parse(tokenize("3 * -2").unwrap()).unwrap();
+    let termdash = &tree.children[0].children[1];
+    assert_eq!(labels(&termdash.children), ["STAR", "NEG", "TERMDASH"]);
+}
+
+#[test]
+fn peek_looks_one_past_current_without_advancing() {
+    let parser = PARSER::constructor(tokenize("f (").unwrap());
+    assert_eq!(parser.current_token(), &TOKEN::IDENTIFIER("f".into()));
+    assert_eq!(parser.peek_token(), &TOKEN::BOPEN);
+    assert_eq!(parser.current_token(), &TOKEN::IDENTIFIER("f".into()));
+
+    let parser = PARSER::constructor(tokenize("").unwrap());
+    assert_eq!(parser.peek_token(), &TOKEN::EOF);
+}
+
+#[test]
+fn percent_sits_in_termdash_left_to_right() {
+    let tree = parse(tokenize("10 % 4").unwrap()).unwrap();
+    let termdash = &tree.children[0].children[1];
+    assert_eq!(labels(&termdash.children), ["PERCENT", "POWER", "TERMDASH"]);
+
+    let tree = parse(tokenize("7 % 3 * 2").unwrap()).unwrap();
+    let first = &tree.children[0].children[1];
+    assert_eq!(first.children[0].label, "PERCENT");
+    assert_eq!(first.children[2].children[0].label, "STAR");
+}
+
+#[test]
+fn unclosed_paren_reports_where_it_was_opened() {
+    let err = parse_positioned("1 * (1 + 2").unwrap_err();
+    let ParseError::MissingClosingParen { opened_at: Some(opened), at: Some(at) } = err else {
+        panic!("expected a positioned MissingClosingParen, got {:?}", err);
+    };
+    assert_eq!((opened.line, opened.column), (1, 5));
+    assert_eq!((at.line, at.column), (1, 11));
+    assert_eq!(err.to_string(), "unclosed '(' opened at line 1, column 5");
+}
+
+#[test]
+fn square_brackets_and_braces_group_like_parentheses() {
+    let tree = parse_str("[1+2]*3").unwrap();
+    let factor = &tree.children[0].children[0].children[0];
+    assert_eq!(labels(&factor.children), ["SQOPEN", "EXPR", "SQCLOSE"]);
+    let braced = to_infix(&parse_str("{a - [b]}").unwrap()).unwrap();
+    assert_eq!(braced, to_infix(&parse_str("(a - (b))").unwrap()).unwrap());
+}
+
+#[test]
+fn a_bracket_must_be_closed_by_its_own_kind() {
+    let err = parse_positioned("(1+2]").unwrap_err();
+    let ParseError::MismatchedBracket { open: '(', found: ']', at: Some(at), .. } = err else {
+        panic!("expected a MismatchedBracket, got {:?}", err);
+    };
+    assert_eq!((at.line, at.column), (1, 5));
+    assert_eq!(err.to_string(), "'(' opened at line 1, column 1 is closed by ']'");
+
+    let err = parse_positioned("2 * [x").unwrap_err();
+    assert!(matches!(err, ParseError::UnclosedBracket { open: '[', .. }));
+    assert_eq!(err.to_string(), "unclosed '[' opened at line 1, column 5");
+    let err = parse(tokenize("{1").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "missing closing bracket for '{'");
+}
+
+#[test]
+fn recovering_parse_consumes_a_mismatched_closer() {
+    let (_, errors) = parse_recovering(tokenize("{1] + (2").unwrap());
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, ["'{' is closed by ']'", "missing closing parenthesis"]);
+}
+
+#[test]
+fn invalid_character_is_named_with_its_position() {
+    let err = parse_positioned("1 @ 2").unwrap_err();
+    assert_eq!(err.to_string(), "invalid character '@'");
+    let at = err.position().unwrap();
+    assert_eq!((at.line, at.column), (1, 3));
+
+    let err = parse_positioned("1 + @").unwrap_err();
+    assert!(matches!(err, ParseError::InvalidCharacter { found: '@', .. }));
+}
+
+#[test]
+fn comparison_sits_above_expr() {
+    let tree = parse(tokenize("1 < 2").unwrap()).unwrap();
+    assert_eq!(tree.label, "COMPARISON");
+    assert_eq!(labels(&tree.children), ["EXPR", "LT", "EXPR"]);
+
+    let tree = parse(tokenize("a <= b + c").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["EXPR", "LE", "EXPR"]);
+    let rhs_dash = &tree.children[2].children[1];
+    assert_eq!(rhs_dash.children[0].label, "PLUS");
+}
+
+#[test]
+fn comparisons_do_not_chain() {
+    let err = parse(tokenize("1 < 2 < 3").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "unexpected trailing token '<'");
+    assert_eq!(parse(tokenize("(1 < 2) < 3").unwrap()).unwrap().label, "COMPARISON");
+}
+
+#[test]
+fn assignment_takes_an_identifier_and_a_value() {
+    let tree = parse(tokenize("x = 5").unwrap()).unwrap();
+    assert_eq!(tree.label, "ASSIGN");
+    assert_eq!(labels(&tree.children), ["IDENTIFIER(x)", "EXPR"]);
+
+    // a comparison is still a plain expression, not an assignment
+    assert_eq!(parse(tokenize("x == 5").unwrap()).unwrap().label, "COMPARISON");
+}
+
+#[test]
+fn assignment_is_right_associative() {
+    let tree = parse(tokenize("a = b = 1").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["IDENTIFIER(a)", "ASSIGN"]);
+    assert_eq!(labels(&tree.children[1].children), ["IDENTIFIER(b)", "EXPR"]);
+
+    // only an identifier can be assigned to
+    let err = parse(tokenize("1 = 2").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "unexpected trailing token '='");
+}
+
+#[test]
+fn semicolons_separate_statements_in_a_program() {
+    let tree = parse(tokenize("1+2; 3*4;").unwrap()).unwrap();
+    assert_eq!(tree.label, "PROGRAM");
+    assert_eq!(labels(&tree.children), ["EXPR", "EXPR"]);
+
+    let tree = parse(tokenize("x = 1; x").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["ASSIGN", "EXPR"]);
+
+    // an empty statement is still an error
+    let err = parse(tokenize("1;;2").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "expected a factor, found ';'");
+}
+
+// the CALL node under a single-factor expression
+fn only_call(tree: &NODE) -> &NODE {
+    let factor = &only_power(tree).children[0];
+    assert_eq!(factor.label, "FACTOR");
+    &factor.children[0]
+}
+
+#[test]
+fn identifier_before_paren_is_a_call() {
+    let tree = parse(tokenize("f(x)").unwrap()).unwrap();
+    assert_eq!(labels(&only_call(&tree).children), ["IDENTIFIER(f)", "BOPEN", "EXPR", "BCLOSE"]);
+
+    let tree = parse(tokenize("g(1, 2+3)").unwrap()).unwrap();
+    let call = only_call(&tree);
+    let expected = ["IDENTIFIER(g)", "BOPEN", "EXPR", "COMMA", "EXPR", "BCLOSE"];
+    assert_eq!(labels(&call.children), expected);
+    assert_eq!(call.children[4].children[1].children[0].label, "PLUS");
+
+    let tree = parse(tokenize("h()").unwrap()).unwrap();
+    assert_eq!(labels(&only_call(&tree).children), ["IDENTIFIER(h)", "BOPEN", "BCLOSE"]);
+}
+
+#[test]
+fn unfinished_argument_lists_are_errors() {
+    let err = parse_positioned("f(1, 2").unwrap_err();
+    assert_eq!(err.to_string(), "unclosed '(' opened at line 1, column 2");
+    let err = parse(tokenize("f(1,)").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "expected a factor, found ')'");
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    let tree = parse(tokenize("a && b || c").unwrap()).unwrap();
+    assert_eq!(tree.label, "DISJUNCTION");
+    assert_eq!(labels(&tree.children), ["CONJUNCTION", "OR", "EXPR"]);
+    assert_eq!(labels(&tree.children[0].children), ["EXPR", "AND", "EXPR"]);
+
+    let tree = parse(tokenize("a || b && c").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["EXPR", "OR", "CONJUNCTION"]);
+
+    // both sit below the comparisons
+    let tree = parse(tokenize("x < 1 && y").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["COMPARISON", "AND", "EXPR"]);
+}
+
+#[test]
+fn logical_chains_are_left_associative() {
+    let tree = parse(tokenize("a || b || c").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["DISJUNCTION", "OR", "EXPR"]);
+}
+
+#[test]
+fn not_wraps_its_unary_operand() {
+    let tree = parse(tokenize("!a").unwrap()).unwrap();
+    let not = only_power(&tree);
+    assert_eq!(not.label, "NOT");
+    assert_eq!(labels(&not.children), ["BANG", "POWER"]);
+
+    let tree = parse(tokenize("!(x < y)").unwrap()).unwrap();
+    let inside = &only_power(&tree).children[1].children[0].children[1];
+    assert_eq!(inside.label, "COMPARISON");
+
+    // ! binds tighter than &&
+    let tree = parse(tokenize("!a && b").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["EXPR", "AND", "EXPR"]);
+    assert_eq!(only_power(&tree.children[0]).label, "NOT");
+}
+
+#[test]
+fn ternary_holds_condition_then_and_else() {
+    let tree = parse(tokenize("a < b ? 1 : 2").unwrap()).unwrap();
+    assert_eq!(tree.label, "TERNARY");
+    assert_eq!(labels(&tree.children), ["COMPARISON", "EXPR", "EXPR"]);
+}
+
+#[test]
+fn ternary_nests_on_the_right() {
+    let tree = parse(tokenize("a ? b : c ? d : e").unwrap()).unwrap();
+    assert_eq!(labels(&tree.children), ["EXPR", "EXPR", "TERNARY"]);
+    assert_eq!(labels(&tree.children[2].children), ["EXPR", "EXPR", "EXPR"]);
+}
+
+#[test]
+fn ternary_without_a_colon_is_an_error() {
+    let err = parse_positioned("a ? b c").unwrap_err();
+    assert!(matches!(err, ParseError::MissingColon { .. }));
+    assert_eq!(err.to_string(), "missing ':' for the '?' at line 1, column 3");
+    assert_eq!(err.position().unwrap().column, 7);
+}
+
+// every leaf label, left to right
+fn leaves(node: &NODE) -> Vec<String> {
+    if node.children.is_empty() {
+        return vec![node.label.clone()];
+    }
+    node.children.iter().flat_map(leaves).collect()
+}
+
+#[test]
+fn recovering_parse_keeps_the_numbers_around_a_bad_token() {
+    let mut scanner = SCANNER::constructor("1 + @ + 2".to_string());
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    let (tree, errors) = PARSER::constructor(tokens).with_positions(positions).parse_recovering();
+    let found: Vec<String> =
+        leaves(&tree).into_iter().filter(|l| l.starts_with("NUMBER") || l == "ERROR").collect();
+    assert_eq!(found, ["NUMBER(1)", "ERROR", "NUMBER(2)"]);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_string(), "invalid character '@'");
+    assert_eq!(errors[0].position().unwrap().column, 5);
+}
+
+#[test]
+fn recovering_parse_patches_missing_parens_and_collects_every_error() {
+    let (tree, errors) = parse_recovering(tokenize("(1 + * 2 + 3").unwrap());
+    let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(messages, ["expected a factor, found '*'", "missing closing parenthesis"]);
+    let factor = only_power(&tree).children[0].clone();
+    assert_eq!(labels(&factor.children), ["BOPEN", "EXPR", "ERROR"]);
+
+    // a clean line recovers nothing
+    let (_, errors) = parse_recovering(tokenize("1 + 2").unwrap());
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn trees_compare_structurally() {
+    let epsilon = || NODE::leaf("EPSILON");
+    let built = NODE::with(
+        "EXPR",
+        vec![
+            NODE::with(
+                "TERM",
+                vec![
+                    NODE::with(
+                        "POWER",
+                        vec![
+                            NODE::with("FACTOR", vec![NODE::leaf("IDENTIFIER(x)")]),
+                            NODE::with("POWERDASH", vec![epsilon()]),
+                        ],
+                    ),
+                    NODE::with("TERMDASH", vec![epsilon()]),
+                ],
+            ),
+            NODE::with("EXPRDASH", vec![epsilon()]),
+        ],
+    );
+    assert_eq!(parse(tokenize("x").unwrap()).unwrap(), built);
+    let tree_of = |input: &str| parse(tokenize(input).unwrap()).unwrap();
+    assert_eq!(tree_of("(x)"), tree_of("( x )"));
+    assert_ne!(parse(tokenize("y").unwrap()).unwrap(), built);
+}
+
+#[test]
+fn lenient_parsing_keeps_the_prefix_and_reports_the_rest() {
+    let mut parser = PARSER::constructor(tokenize("1 2").unwrap()).strict(false);
+    let tree = parser.parse().unwrap();
+    assert_eq!(tree, parse(tokenize("1").unwrap()).unwrap());
+    let leftover = parser.leftover().unwrap();
+    assert_eq!(leftover.to_string(), "unexpected trailing token '2'");
+
+    // the default is strict
+    let err = PARSER::constructor(tokenize("1 2").unwrap()).parse().unwrap_err();
+    assert_eq!(err.to_string(), leftover.to_string());
+    // a complete parse leaves nothing over
+    let mut whole = PARSER::constructor(tokenize("1 + 2").unwrap()).strict(false);
+    whole.parse().unwrap();
+    assert!(whole.leftover().is_none());
+}
+
+#[test]
+fn parse_str_scans_and_parses_in_one_call() {
+    let tree = parse_str("1+2").unwrap();
+    assert_eq!(tree.label, "EXPR");
+    assert_eq!(tree, parse(tokenize("1+2").unwrap()).unwrap());
+
+    // only the first line is read, and it must be used up
+    assert_eq!(parse_str("x\n(").unwrap(), parse_str("x").unwrap());
+    let err = parse_str("1 2").unwrap_err();
+    assert_eq!(err.to_string(), "unexpected trailing token '2'");
+    assert!(err.position().is_some());
+    assert!(matches!(parse_str("1 + @"), Err(ParseError::InvalidCharacter { found: '@', .. })));
+    // an unclosed comment is not confused with a disabled '/'
+    let err = parse_str("1 + /* 2").unwrap_err();
+    assert!(matches!(err, ParseError::UnterminatedComment { .. }));
+    assert_eq!(err.to_string(), "unterminated block comment");
+}
+
+#[test]
+fn nodes_span_the_source_they_were_parsed_from() {
+    let tree = parse_str("1 + 2").unwrap();
+    assert_eq!((tree.label.as_str(), tree.span), ("EXPR", Some((0, 5))));
+    let number = &tree.children[0].children[0].children[0].children[0];
+    assert_eq!((number.label.as_str(), number.span), ("NUMBER(1)", Some((0, 1))));
+    let exprdash = &tree.children[1];
+    assert_eq!(exprdash.span, Some((2, 5)));
+    assert_eq!(exprdash.children[0].span, Some((2, 3)));
+    // EPSILON covers nothing
+    assert_eq!(exprdash.children[2].children[0].span, None);
+
+    // offsets count chars, and parentheses are part of the FACTOR they close
+    let tree = parse_str(" (é) ^ 2").unwrap();
+    assert_eq!(tree.span, Some((1, 8)));
+    let factor = &tree.children[0].children[0].children[0];
+    assert_eq!(factor.span, Some((1, 4)));
+    assert_eq!(factor.children[1].span, Some((2, 3)));
+
+    // without spans attached every node has none
+    assert_eq!(parse(tokenize("1 + 2").unwrap()).unwrap().span, None);
+}
+
+#[test]
+fn top_level_commas_make_an_expression_list() {
+    let tree = parse(tokenize("1, 2+3, 4").unwrap()).unwrap();
+    assert_eq!(tree.label, "EXPRLIST");
+    assert_eq!(labels(&tree.children), ["EXPR", "EXPR", "EXPR"]);
+    assert_eq!(tree.children[1], parse(tokenize("2+3").unwrap()).unwrap());
+
+    // lists sit under ';' and above '='
+    let program = parse(tokenize("a = 1, b = 2; 3").unwrap()).unwrap();
+    assert_eq!(labels(&program.children), ["EXPRLIST", "EXPR"]);
+    assert_eq!(labels(&program.children[0].children), ["ASSIGN", "ASSIGN"]);
+
+    let err = parse(tokenize("1, 2,").unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "expected a factor, found '<eof>'");
+    // inside parentheses a ',' is not a list separator
+    assert!(parse(tokenize("(1, 2)").unwrap()).is_err());
+}
+
+// runs on the test thread's own 2 MB stack, which the default limit must fit
+#[test]
+fn deep_nesting_is_a_clean_error_not_a_stack_overflow() {
+    let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    let err = parse_positioned(&nested(10_000)).unwrap_err();
+    assert!(matches!(err, ParseError::TooDeep { limit: DEFAULT_MAX_DEPTH, .. }));
+    assert_eq!(err.to_string(), "expression nested more than 64 levels deep");
+    // reported at what would have been inside the 65th '('
+    assert_eq!(err.position().map(|at| at.column), Some(66));
+
+    // up to the limit is fine, and the limit can be changed
+    assert!(parse(tokenize(&nested(DEFAULT_MAX_DEPTH)).unwrap()).is_ok());
+    let mut shallow = PARSER::constructor(tokenize(&nested(3)).unwrap()).max_depth(2);
+    assert!(matches!(shallow.parse(), Err(ParseError::TooDeep { limit: 2, .. })));
+    // flat chains are read in a loop, so any length parses and evaluates
+    let sum = vec!["1"; 10_000].join(" + ");
+    let tree = parse(tokenize(&sum).unwrap()).unwrap();
+    assert_eq!(eval(&tree), Ok(10_000.0));
+    let product = vec!["1"; 10_000].join(" * ");
+    assert!(parse(tokenize(&product).unwrap()).is_ok());
+    let (_, errors) = parse_recovering(tokenize(&"-".repeat(10_000)).unwrap());
+    assert!(matches!(errors.as_slice(), [ParseError::TooDeep { .. }]));
+}
+
+#[test]
+fn iterative_parens_build_the_same_trees_as_recursion() {
+    let inputs = [
+        "(1)",
+        "(((x)))",
+        "((1) - 2) * 3",
+        "((1)+(2))^(-(3))",
+        "f((x), ((y)))",
+        "-((1))",
+        "((a ? (b) : c)) || ((d) < 2)",
+        "x = ((1), 2)",
+        "((1)(2))",
+        "((1) 2)",
+        "((1)",
+        "(()",
+        "((1 +) + 2)",
+        "[({x})] * {[2]}",
+        "([1)]",
+        "{(1}",
+    ];
+    for input in inputs {
+        let recursive = PARSER::constructor(tokenize(input).unwrap()).parse();
+        let iterative = PARSER::constructor(tokenize(input).unwrap()).iterative(true).parse();
+        match (recursive, iterative) {
+            (Ok(a), Ok(b)) => assert_eq!(a, b, "{}", input),
+            (Err(a), Err(b)) => assert_eq!(a.to_string(), b.to_string(), "{}", input),
+            (a, b) => panic!("{}: {:?} vs {:?}", input, a, b),
+        }
+        let recovering = PARSER::constructor(tokenize(input).unwrap()).parse_recovering();
+        let mut parser = PARSER::constructor(tokenize(input).unwrap()).iterative(true);
+        let iterative = parser.parse_recovering();
+        assert_eq!(recovering.0, iterative.0, "{}", input);
+        assert_eq!(format!("{:?}", recovering.1), format!("{:?}", iterative.1), "{}", input);
+    }
+}
+
+#[test]
+fn iterative_parens_handle_any_depth() {
+    let depth = 100_000;
+    let input = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    let tree = PARSER::constructor(tokenize(&input).unwrap()).iterative(true).parse().unwrap();
+
+    // walk down EXPR TERM POWER FACTOR once per '(' without recursing
+    let mut node = &tree;
+    for _ in 0..depth {
+        let factor = &node.children[0].children[0].children[0];
+        assert_eq!(labels(&factor.children), ["BOPEN", "EXPR", "BCLOSE"]);
+        node = &factor.children[1];
+    }
+    assert_eq!(node.children[0].children[0].children[0].children[0].label, "NUMBER(1)");
+}
+
+#[test]
+fn right_assoc_additive_groups_plus_chains_to_the_right() {
+    let parse_with = |input: &str, right: bool| {
+        PARSER::constructor(tokenize(input).unwrap()).right_assoc_additive(right).parse().unwrap()
+    };
+    // left: EXPRDASH(+, TERM(2), EXPRDASH(+, TERM(3), ε)), a flat chain under EXPR
+    let left = parse_with("1+2+3", false);
+    assert_eq!(labels(&left.children[1].children), ["PLUS", "TERM", "EXPRDASH"]);
+    assert_eq!(to_infix(&left).unwrap(), "1 + 2 + 3");
+    // right: EXPRDASH(+, EXPR(2 + 3)), so the second + sits inside its own EXPR
+    let right = parse_with("1+2+3", true);
+    let rest = &right.children[1].children;
+    assert_eq!(labels(rest), ["PLUS", "EXPR"]);
+    assert_eq!(labels(&rest[1].children[1].children), ["PLUS", "EXPR"]);
+    assert_eq!(to_infix(&right).unwrap(), "1 + (2 + 3)");
+
+    // the grouping shows once the operator is not associative
+    assert_eq!(eval(&parse_with("1-2-3", false)), Ok(-4.0));
+    assert_eq!(eval(&parse_with("1-2-3", true)), Ok(2.0));
+    assert_eq!(eval(&parse_with("1+2*3-4", true)), Ok(1.0 + (6.0 - 4.0)));
+}
+
+#[test]
+fn remaining_is_what_a_lenient_parse_left_behind() {
+    let mut parser = PARSER::constructor(tokenize("1+2 ) )").unwrap()).strict(false);
+    assert_eq!(parser.remaining().len(), 6);
+    parser.parse().unwrap();
+    assert_eq!(parser.remaining(), [TOKEN::BCLOSE, TOKEN::BCLOSE, TOKEN::EOF]);
+
+    let mut parser = PARSER::constructor(tokenize("1+2").unwrap());
+    parser.parse().unwrap();
+    assert_eq!(parser.remaining(), [TOKEN::EOF]);
+}
+
+#[test]
+fn bang_after_a_factor_is_postfix_factorial() {
+    let five = parse_str("5!").unwrap();
+    let power = only_power(&five);
+    assert_eq!(labels(&power.children), ["FACTORIAL", "POWERDASH"]);
+    assert_eq!(labels(&power.children[0].children), ["FACTOR"]);
+    assert_eq!(power.children[0].span, Some((0, 2)));
+
+    let grouped = parse_str("(1+2)!").unwrap();
+    let factorial = &only_power(&grouped).children[0];
+    assert_eq!(factorial.label, "FACTORIAL");
+    assert_eq!(labels(&factorial.children[0].children), ["BOPEN", "EXPR", "BCLOSE"]);
+
+    // where a factor should start, '!' is still prefix NOT
+    let not = parse_str("!x").unwrap();
+    assert_eq!(labels(&not.children[0].children), ["NOT", "TERMDASH"]);
+    assert_eq!(labels(&not.children[0].children[0].children), ["BANG", "POWER"]);
+    // and != is its own token
+    assert_eq!(parse_str("5 != 3!").unwrap().label, "COMPARISON");
+    // a '!' with no factor before it wants an operand after it
+    let err = parse_str("5 + !").unwrap_err();
+    assert_eq!(err.to_string(), "expected a factor, found '<eof>'");
+}
+
+#[test]
+fn errors_at_end_of_input_point_just_past_it() {
+    // EOF carries the position after the last char, so running out of input
+    // is reported there rather than at the last real token
+    for input in ["1 +", "1 +   ", "(1 + é", "x = 2 *\n"] {
+        let err = parse_positioned(input).unwrap_err();
+        let at = err.position().unwrap();
+        assert_eq!(at.offset, input.len(), "{:?}", input);
+    }
+    let at = parse_positioned("1 +").unwrap_err().position().unwrap();
+    assert_eq!((at.line, at.column), (1, 4));
+    let at = parse_positioned("x = 2 *\n").unwrap_err().position().unwrap();
+    assert_eq!((at.line, at.column), (2, 1));
+}
+
+#[test]
+fn render_error_puts_a_caret_under_the_column() {
+    let err = parse_positioned("1 ) 2").unwrap_err();
+    assert_eq!(err.position().map(|p| p.column), Some(3));
+    let rendered = render_error(&err, "1 ) 2");
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines, ["Error at line 1, column 3: unexpected trailing token ')'", "1 ) 2", "  ^"]);
+
+    // the line is picked out of a longer source, and a tab keeps its width
+    let source = "1 +\n\t(3 *";
+    let err = parse_positioned(source).unwrap_err();
+    let rendered = render_error(&err, source);
+    assert_eq!(rendered.lines().skip(1).collect::<Vec<_>>(), ["\t(3 *", "\t    ^"]);
+    // with no position there is nothing to point at
+    let err = parse(tokenize("1 +").unwrap()).unwrap_err();
+    assert_eq!(render_error(&err, "1 +"), "Error: expected a factor, found '<eof>'");
+}
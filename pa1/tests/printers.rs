@@ -0,0 +1,313 @@
+use pal::{
+    NODE, PARSER, assign_ids, ast_to_node, bfs_print, count_nodes, parse, print_postorder,
+    print_tree_indented, print_tree_indented_by, prune_epsilon, to_ast, to_dot, to_infix, to_json,
+    to_sexpr, tokenize, tree_depth,
+};
+use serde_json::Value;
+
+#[test]
+fn sexpr_nests_children_in_parentheses() {
+    let tree = parse(tokenize("1+2").unwrap()).unwrap();
+    let expected = "(EXPR \
+        (TERM (POWER (FACTOR NUMBER(1)) (POWERDASH EPSILON)) (TERMDASH EPSILON)) \
+        (EXPRDASH PLUS \
+        (TERM (POWER (FACTOR NUMBER(2)) (POWERDASH EPSILON)) (TERMDASH EPSILON)) \
+        (EXPRDASH EPSILON)))";
+    assert_eq!(to_sexpr(&tree), expected);
+}
+
+#[test]
+fn dot_output_is_a_digraph_with_edges() {
+    let tree = parse(tokenize("1+2").unwrap()).unwrap();
+    let dot = to_dot(&tree);
+    assert!(dot.starts_with("digraph {"));
+    assert!(dot.trim_end().ends_with('}'));
+    assert!(dot.contains("  n0 [label=\"EXPR\"];"));
+    assert!(dot.contains("  n0 -> n1;"));
+    // one edge per non-root node
+    let nodes = dot.lines().filter(|l| l.contains("[label=")).count();
+    let edges = dot.lines().filter(|l| l.contains("->")).count();
+    assert_eq!(edges, nodes - 1);
+}
+
+#[test]
+fn dot_labels_are_escaped() {
+    let tree = NODE::with("say \"hi\"", vec![NODE::leaf("back\\slash")]);
+    let dot = to_dot(&tree);
+    assert!(dot.contains(r#"n0 [label="say \"hi\""];"#));
+    assert!(dot.contains(r#"n1 [label="back\\slash"];"#));
+}
+
+#[test]
+fn json_nests_objects_down_to_the_leaf() {
+    let tree = parse(tokenize("1").unwrap()).unwrap();
+    let json: Value = serde_json::from_str(&to_json(&tree)).unwrap();
+    assert_eq!(json["label"], "EXPR");
+    let factor = &json["children"][0]["children"][0]["children"][0];
+    assert_eq!(factor["label"], "FACTOR");
+    assert_eq!(factor["children"][0]["label"], "NUMBER(1)");
+    assert_eq!(factor["children"][0]["children"], Value::Array(vec![]));
+}
+
+#[test]
+fn ids_number_the_tree_in_pre_order() {
+    let mut tree = prune_epsilon(&parse(tokenize("1+2").unwrap()).unwrap());
+    assert_eq!(assign_ids(&mut tree), 11);
+    // EXPR
+    //   TERM > POWER > FACTOR > NUMBER(1)
+    //   EXPRDASH
+    //     PLUS
+    //     TERM > POWER > FACTOR > NUMBER(2)
+    let mut order = Vec::new();
+    let mut pending = vec![&tree];
+    while let Some(node) = pending.pop() {
+        order.push((node.id, node.label.as_str()));
+        pending.extend(node.children.iter().rev());
+    }
+    let expected = [
+        "EXPR", "TERM", "POWER", "FACTOR", "NUMBER(1)", "EXPRDASH", "PLUS", "TERM", "POWER",
+        "FACTOR", "NUMBER(2)",
+    ];
+    assert_eq!(order, expected.into_iter().enumerate().collect::<Vec<_>>());
+
+    // the exports name nodes by these ids
+    let dot = to_dot(&tree);
+    assert!(dot.contains("  n5 [label=\"EXPRDASH\"];"));
+    assert!(dot.contains("  n5 -> n6;"));
+    let json: Value = serde_json::from_str(&to_json(&tree)).unwrap();
+    assert_eq!(json["children"][1]["id"], 5);
+}
+
+#[test]
+fn library_trees_come_numbered() {
+    let full = parse(tokenize("1+2").unwrap()).unwrap();
+    let exprdash = &full.children[1];
+    assert_eq!(exprdash.label, "EXPRDASH");
+    assert_eq!(exprdash.children[0].id, exprdash.id + 1);
+    // pruning renumbers the smaller tree
+    let pruned = prune_epsilon(&full);
+    assert_eq!((pruned.children[1].label.as_str(), pruned.children[1].id), ("EXPRDASH", 5));
+    let ast = ast_to_node(&to_ast(&full).unwrap());
+    let ids: Vec<usize> = ast.children.iter().map(|n| n.id).collect();
+    assert_eq!((ast.id, ids), (0, vec![1, 2]));
+}
+
+#[test]
+fn exports_number_hand_built_trees_without_assign_ids() {
+    let leaf = || NODE::leaf("x");
+    let tree = NODE::with("root", vec![NODE::with("mid", vec![leaf()]), leaf()]);
+    assert!(tree.children.iter().all(|n| n.id == 0));
+    let dot = to_dot(&tree);
+    let edges: Vec<&str> = dot.lines().filter(|l| l.contains("->")).collect();
+    assert_eq!(edges, ["  n1 -> n2;", "  n0 -> n1;", "  n0 -> n3;"]);
+    let json: Value = serde_json::from_str(&to_json(&tree)).unwrap();
+    assert_eq!(json["children"][0]["children"][0]["id"], 2);
+    assert_eq!(json["children"][1]["id"], 3);
+}
+
+#[test]
+fn json_labels_are_escaped() {
+    let tree = NODE::leaf("a \"quoted\" (label)\\");
+    let json: Value = serde_json::from_str(&to_json(&tree)).unwrap();
+    assert_eq!(json["label"], "a \"quoted\" (label)\\");
+}
+
+#[test]
+fn pruning_removes_epsilon_and_empty_dash_nodes() {
+    let tree = parse(tokenize("1+2").unwrap()).unwrap();
+    let expected = "(EXPR (TERM (POWER (FACTOR NUMBER(1)))) \
+        (EXPRDASH PLUS (TERM (POWER (FACTOR NUMBER(2))))))";
+    assert_eq!(to_sexpr(&prune_epsilon(&tree)), expected);
+}
+
+#[test]
+fn bfs_print_writes_one_line_per_level() {
+    let tree = parse(tokenize("1+2").unwrap()).unwrap();
+    let mut out = Vec::new();
+    bfs_print(&tree, &mut out).unwrap();
+    let expected = "EXPR\n\
+        TERM EXPRDASH\n\
+        POWER TERMDASH PLUS TERM EXPRDASH\n\
+        FACTOR POWERDASH EPSILON POWER TERMDASH EPSILON\n\
+        NUMBER(1) EPSILON FACTOR POWERDASH EPSILON\n\
+        NUMBER(2) EPSILON\n";
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn indented_printer_outlines_the_tree() {
+    let tree = parse(tokenize("1+2").unwrap()).unwrap();
+    let mut out = Vec::new();
+    print_tree_indented(&tree, &mut out).unwrap();
+    let expected = "\
+EXPR
+  TERM
+    POWER
+      FACTOR
+        NUMBER(1)
+      POWERDASH
+        EPSILON
+    TERMDASH
+      EPSILON
+  EXPRDASH
+    PLUS
+    TERM
+      POWER
+        FACTOR
+          NUMBER(2)
+        POWERDASH
+          EPSILON
+      TERMDASH
+        EPSILON
+    EXPRDASH
+      EPSILON
+";
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+fn infix(input: &str) -> String {
+    to_infix(&parse(tokenize(input).unwrap()).unwrap()).unwrap()
+}
+
+#[test]
+fn infix_keeps_only_the_parentheses_precedence_needs() {
+    assert_eq!(infix("1+2*3"), "1 + 2 * 3");
+    assert_eq!(infix("(1+2)*3"), "(1 + 2) * 3");
+    assert_eq!(infix("((a))*(b*c)"), "a * (b * c)");
+    assert_eq!(infix("(10-3)-2"), "10 - 3 - 2");
+    assert_eq!(infix("10-(3-2)"), "10 - (3 - 2)");
+}
+
+#[test]
+fn infix_respects_power_and_negation() {
+    assert_eq!(infix("2^3^2"), "2 ^ 3 ^ 2");
+    assert_eq!(infix("(2^3)^2"), "(2 ^ 3) ^ 2");
+    assert_eq!(infix("-2^2"), "-2 ^ 2");
+    assert_eq!(infix("(-2)^2"), "(-2) ^ 2");
+    assert_eq!(infix("-(a+b) < 3"), "-(a + b) < 3");
+}
+
+#[test]
+fn infix_output_parses_back_to_the_same_infix() {
+    for input in ["(1+2)*3", "a % (b - c) ^ (-d)", "(x < y) == 1"] {
+        let once = infix(input);
+        assert_eq!(infix(&once), once, "from {}", input);
+    }
+}
+
+#[test]
+fn infix_prints_assignments_unparenthesized() {
+    assert_eq!(infix("a = b = (1 + 2) * 3"), "a = b = (1 + 2) * 3");
+}
+
+#[test]
+fn infix_separates_list_items_with_commas() {
+    assert_eq!(infix("1+2, (3)*4, a = 5; x"), "1 + 2, 3 * 4, a = 5; x");
+}
+
+#[test]
+fn infix_prints_calls_with_their_arguments() {
+    assert_eq!(infix("f() + g(x,(1+2)*3)"), "f() + g(x, (1 + 2) * 3)");
+}
+
+#[test]
+fn infix_groups_logical_operators_by_precedence() {
+    assert_eq!(infix("(a && b) || c"), "a && b || c");
+    assert_eq!(infix("a && (b || c)"), "a && (b || c)");
+    assert_eq!(infix("x < 1 && y == (2 || z)"), "x < 1 && y == (2 || z)");
+    assert_eq!(infix("!(a && b) || !-c"), "!(a && b) || !-c");
+}
+
+#[test]
+fn infix_prints_ternaries_right_nested() {
+    assert_eq!(infix("a ? b : (c ? d : e)"), "a ? b : c ? d : e");
+    assert_eq!(infix("(a ? b : c) ? d : e"), "(a ? b : c) ? d : e");
+    assert_eq!(infix("(x || y) ? 1 + 2 : -3"), "x || y ? 1 + 2 : -3");
+}
+
+#[test]
+fn postorder_printer_lists_children_before_parents() {
+    let tree = prune_epsilon(&parse(tokenize("1+2").unwrap()).unwrap());
+    let mut out = Vec::new();
+    print_postorder(&tree, &mut out).unwrap();
+    let order: Vec<String> = String::from_utf8(out).unwrap().lines().map(String::from).collect();
+    let expected = [
+        "NUMBER(1)", "FACTOR", "POWER", "TERM", "PLUS", "NUMBER(2)", "FACTOR", "POWER", "TERM",
+        "EXPRDASH", "EXPR",
+    ];
+    assert_eq!(order, expected);
+}
+
+#[test]
+fn indent_width_is_configurable() {
+    let tree = prune_epsilon(&parse(tokenize("-x").unwrap()).unwrap());
+    let outline = |indent: usize| {
+        let mut out = Vec::new();
+        print_tree_indented_by(&tree, indent, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let by_two = "\
+EXPR
+  TERM
+    NEG
+      MINUS
+      POWER
+        FACTOR
+          IDENTIFIER(x)
+";
+    let by_four = "\
+EXPR
+    TERM
+        NEG
+            MINUS
+            POWER
+                FACTOR
+                    IDENTIFIER(x)
+";
+    assert_eq!(outline(2), by_two);
+    assert_eq!(outline(4), by_four);
+
+    // the default is two, and zero is bumped to one
+    let mut default = Vec::new();
+    print_tree_indented(&tree, &mut default).unwrap();
+    assert_eq!(String::from_utf8(default).unwrap(), by_two);
+    assert_eq!(outline(0), outline(1));
+}
+
+#[test]
+fn ast_prints_breadth_first_without_scaffolding() {
+    let tree = parse(tokenize("1+2*3").unwrap()).unwrap();
+    let mut out = Vec::new();
+    bfs_print(&ast_to_node(&to_ast(&tree).unwrap()), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "Add\nNum(1) Mul\nNum(2) Num(3)\n");
+
+    let call = ast_to_node(&to_ast(&parse(tokenize("x = f(-y, 2)").unwrap()).unwrap()).unwrap());
+    assert_eq!(to_sexpr(&call), "(Assign(x) (Call(f) (Neg Var(y)) Num(2)))");
+}
+
+#[test]
+fn deep_trees_prune_measure_and_print_without_recursion() {
+    // far deeper than any recursive walk could go on a 2 MB test thread
+    let depth = 100_000;
+    let input = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    let tree = PARSER::constructor(tokenize(&input).unwrap()).iterative(true).parse().unwrap();
+    let tree = prune_epsilon(&tree);
+    // EXPR TERM POWER FACTOR per level, then '(' and ')' beside the inner EXPR
+    assert_eq!(tree_depth(&tree), 4 * depth + 4);
+    assert_eq!(count_nodes(&tree), 6 * depth + 5);
+
+    let sexpr = to_sexpr(&tree);
+    assert!(sexpr.starts_with("(EXPR (TERM (POWER (FACTOR BOPEN (EXPR"));
+    assert!(sexpr.contains(" (EXPR (TERM (POWER (FACTOR NUMBER(1))))) BCLOSE)))) BCLOSE))))"));
+    assert!(sexpr.ends_with(" BCLOSE))))"));
+    assert_eq!(sexpr.matches("BCLOSE").count(), depth);
+
+    let json = to_json(&tree);
+    assert_eq!(json.matches("\"label\"").count(), count_nodes(&tree));
+    let dot = to_dot(&tree);
+    assert_eq!(dot.matches(" -> ").count(), count_nodes(&tree) - 1);
+
+    let mut levels = Vec::new();
+    bfs_print(&tree, &mut levels).unwrap();
+    assert_eq!(levels.iter().filter(|&&b| b == b'\n').count(), tree_depth(&tree) + 1);
+}
@@ -0,0 +1,440 @@
+use pal::{
+    POSITION, PARSER, SCANNER, ScanError, ScannerConfig, TOKEN, Token, tokenize,
+    token_histogram, tokenize_borrowed, tokenize_checked,
+};
+
+#[test]
+fn token_display_uses_source_text() {
+    let cases = [
+        (TOKEN::IDENTIFIER("x".into()), "x"),
+        (TOKEN::NUMBER("3.14".into()), "3.14"),
+        (TOKEN::STRING("say \"hi\"\n".into()), r#""say \"hi\"\n""#),
+        (TOKEN::PLUS, "+"),
+        (TOKEN::MINUS, "-"),
+        (TOKEN::STAR, "*"),
+        (TOKEN::SLASH, "/"),
+        (TOKEN::PERCENT, "%"),
+        (TOKEN::POW, "**"),
+        (TOKEN::BOPEN, "("),
+        (TOKEN::BCLOSE, ")"),
+        (TOKEN::ERROR('@'), "@"),
+        (TOKEN::EOF, "<eof>"),
+    ];
+    for (token, text) in cases {
+        assert_eq!(token.to_string(), text);
+    }
+}
+
+#[test]
+fn tokenizes_a_small_expression() {
+    assert_eq!(
+        tokenize("1 + x").unwrap(),
+        vec![TOKEN::NUMBER("1".into()), TOKEN::PLUS, TOKEN::IDENTIFIER("x".into()), TOKEN::EOF]
+    );
+}
+
+#[test]
+fn scanner_iterates_lazily_and_ends_after_eof() {
+    let mut scanner = SCANNER::constructor("1+2".to_string());
+    let first: Vec<TOKEN> = scanner.by_ref().take(3).collect();
+    assert_eq!(first, vec![TOKEN::NUMBER("1".into()), TOKEN::PLUS, TOKEN::NUMBER("2".into())]);
+    assert_eq!(scanner.next(), Some(TOKEN::EOF));
+    assert_eq!(scanner.next(), None);
+}
+
+#[test]
+fn line_comments_are_skipped() {
+    let one_plus_two = tokenize("1 + 2").unwrap();
+    assert_eq!(tokenize("1 + 2 // ignored").unwrap(), one_plus_two);
+    assert_eq!(tokenize("// the whole line").unwrap(), vec![TOKEN::EOF]);
+    // the comment ends at the newline, and lone slashes still divide
+    assert_eq!(tokenize("1 // one\n/ 2").unwrap(), tokenize("1 / 2").unwrap());
+    assert_eq!(tokenize("3 / / 2").unwrap()[1..3], [TOKEN::SLASH, TOKEN::SLASH]);
+}
+
+#[test]
+fn block_comments_are_skipped_mid_line() {
+    assert_eq!(tokenize("1 /* hi */ + 2").unwrap(), tokenize("1 + 2").unwrap());
+    assert_eq!(tokenize("1 /* spans\nlines ** */ * 2").unwrap(), tokenize("1 * 2").unwrap());
+    // no nesting: the first */ closes the comment
+    assert_eq!(tokenize("/* a /* b */ 3").unwrap(), tokenize("3").unwrap());
+}
+
+#[test]
+fn unterminated_block_comment_is_an_error() {
+    let tokens = SCANNER::constructor("1 /* oops".to_string()).tokenize_the_line();
+    assert_eq!(tokens, vec![TOKEN::NUMBER("1".into()), TOKEN::UNCLOSEDCOMMENT, TOKEN::EOF]);
+    let err = tokenize("1 /* oops").unwrap_err();
+    let at = POSITION { line: 1, column: 3, offset: 2 };
+    assert_eq!(err, ScanError::UnterminatedComment { at });
+    assert_eq!(err.to_string(), "unterminated block comment");
+}
+
+#[test]
+fn identifiers_take_underscores_and_digits() {
+    let id = |name: &str| TOKEN::IDENTIFIER(name.into());
+    assert_eq!(tokenize("foo_bar").unwrap(), vec![id("foo_bar"), TOKEN::EOF]);
+    assert_eq!(tokenize("x1").unwrap(), vec![id("x1"), TOKEN::EOF]);
+    assert_eq!(tokenize("_tmp").unwrap(), vec![id("_tmp"), TOKEN::EOF]);
+    assert_eq!(tokenize("__5").unwrap(), vec![id("__5"), TOKEN::EOF]);
+    // but `_5` is a number with a leading separator, which is an error
+    let tokens = SCANNER::constructor("_5 + _5x".to_string()).tokenize_the_line();
+    assert_eq!(tokens, [TOKEN::ERROR('_'), TOKEN::PLUS, TOKEN::ERROR('_'), TOKEN::EOF]);
+    // a leading digit still starts a number
+    assert_eq!(tokenize("1x").unwrap(), vec![TOKEN::NUMBER("1".into()), id("x"), TOKEN::EOF]);
+}
+
+#[test]
+fn percent_scans_as_its_own_token() {
+    let tokens = tokenize("7%3").unwrap();
+    assert_eq!(tokens[1], TOKEN::PERCENT);
+    assert_eq!(tokens.len(), 4);
+}
+
+#[test]
+fn brackets_and_braces_scan_as_their_own_tokens() {
+    assert_eq!(
+        tokenize("[{()}]").unwrap(),
+        [
+            TOKEN::SQOPEN,
+            TOKEN::CBOPEN,
+            TOKEN::BOPEN,
+            TOKEN::BCLOSE,
+            TOKEN::CBCLOSE,
+            TOKEN::SQCLOSE,
+            TOKEN::EOF
+        ]
+    );
+}
+
+#[test]
+fn relational_operators_use_one_character_lookahead() {
+    assert_eq!(
+        tokenize("a<b >c<=d>= e").unwrap(),
+        [
+            TOKEN::IDENTIFIER("a".into()),
+            TOKEN::LT,
+            TOKEN::IDENTIFIER("b".into()),
+            TOKEN::GT,
+            TOKEN::IDENTIFIER("c".into()),
+            TOKEN::LE,
+            TOKEN::IDENTIFIER("d".into()),
+            TOKEN::GE,
+            TOKEN::IDENTIFIER("e".into()),
+            TOKEN::EOF,
+        ]
+    );
+    assert_eq!(tokenize("< =").unwrap(), [TOKEN::LT, TOKEN::ASSIGN, TOKEN::EOF]);
+}
+
+#[test]
+fn equality_operators_need_a_following_equals() {
+    let x = || TOKEN::IDENTIFIER("x".into());
+    assert_eq!(tokenize("x==1").unwrap(), [x(), TOKEN::EQ, TOKEN::NUMBER("1".into()), TOKEN::EOF]);
+    assert_eq!(tokenize("x != 1").unwrap()[1], TOKEN::NE);
+    assert_eq!(tokenize("!x").unwrap(), [TOKEN::BANG, x(), TOKEN::EOF]);
+    assert_eq!(tokenize("! =").unwrap()[..2], [TOKEN::BANG, TOKEN::ASSIGN]);
+    assert_eq!(tokenize("x = 1").unwrap()[1], TOKEN::ASSIGN);
+}
+
+#[test]
+fn tokenize_returns_ok_for_clean_input_and_err_for_a_bad_character() {
+    let tokens = tokenize("a * (2 - b)").unwrap();
+    assert_eq!(tokens.len(), 8);
+    assert_eq!(tokens.last(), Some(&TOKEN::EOF));
+
+    let err = tokenize("1 +\n  @ 2").unwrap_err();
+    let at = POSITION { line: 2, column: 3, offset: 6 };
+    assert_eq!(err, ScanError::InvalidCharacter { found: '@', at });
+    assert_eq!(err.to_string(), "invalid character '@'");
+}
+
+#[test]
+fn strings_scan_with_their_escapes_resolved() {
+    let string = |text: &str| TOKEN::STRING(text.into());
+    assert_eq!(tokenize(r#""hi""#).unwrap(), [string("hi"), TOKEN::EOF]);
+    assert_eq!(tokenize(r#""a\"b""#).unwrap(), [string("a\"b"), TOKEN::EOF]);
+    let tokens = tokenize(r#""1\n2" + "\\""#).unwrap();
+    assert_eq!(tokens[..3], [string("1\n2"), TOKEN::PLUS, string("\\")]);
+}
+
+#[test]
+fn unterminated_string_is_a_scan_error() {
+    let err = tokenize(r#"1 + "oops"#).unwrap_err();
+    let at = POSITION { line: 1, column: 5, offset: 4 };
+    assert_eq!(err, ScanError::UnterminatedString { at });
+    assert_eq!(err.to_string(), "unterminated string");
+    // an escaped quote does not close the string
+    assert!(tokenize(r#""oops\""#).is_err());
+}
+
+#[test]
+fn positions_count_chars_but_also_carry_byte_offsets() {
+    let err = tokenize("é + @").unwrap_err();
+    let at = err.position();
+    // 'é' is one column but two bytes
+    assert_eq!((at.column, at.offset), (5, 5));
+    assert_eq!(&"é + @"[at.offset..], "@");
+
+    let scanner = SCANNER::constructor("aé€b".to_string());
+    let offsets: Vec<usize> = (0..=4).map(|i| scanner.byte_offset(i)).collect();
+    assert_eq!(offsets, [0, 1, 3, 6, 7]);
+}
+
+#[test]
+fn logical_operators_take_two_characters() {
+    let tokens = tokenize("a&&b||c").unwrap();
+    assert_eq!((&tokens[1], &tokens[3]), (&TOKEN::AND, &TOKEN::OR));
+    assert!(matches!(tokenize("a & b"), Err(ScanError::InvalidCharacter { found: '&', .. })));
+    assert!(matches!(tokenize("a | b"), Err(ScanError::InvalidCharacter { found: '|', .. })));
+}
+
+#[test]
+fn large_input_scans_to_the_right_token_count() {
+    // about 8 MB in one string, spread over many lines
+    let line = "total_1 + 0xFF * (x2 - 3.5e1) // note\n";
+    let copies = 8 * 1024 * 1024 / line.len();
+    let input = line.repeat(copies);
+    let mut scanner = SCANNER::constructor(input);
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    assert_eq!(tokens.len(), 9 * copies + 1);
+    let last = positions[tokens.len() - 2];
+    assert_eq!((last.line, last.column), (copies, 29));
+}
+
+#[test]
+fn borrowed_tokens_slice_the_original_input() {
+    let input = String::from("rate_2 * 1_000.5e3 + (x)");
+    let tokens = tokenize_borrowed(&input);
+    assert_eq!(tokens.len(), 8);
+    assert_eq!(tokens[0], Token::Identifier("rate_2"));
+    assert_eq!(tokens[1], Token::Other(TOKEN::STAR));
+    assert_eq!(tokens[2], Token::Number("1_000.5e3"));
+    assert_eq!(tokens[7], Token::Other(TOKEN::EOF));
+
+    // the text is a view into `input`, not a copy
+    let (Token::Identifier(id), Token::Number(n)) = (&tokens[0], &tokens[2]) else {
+        unreachable!();
+    };
+    let range = input.as_bytes().as_ptr_range();
+    assert!(range.contains(&id.as_ptr()) && range.contains(&n.as_ptr()));
+
+    // the owned scanner agrees token for token
+    let owned = tokenize(&input).unwrap();
+    assert_eq!(owned[2], TOKEN::NUMBER("1_000.5e3".into()));
+    assert_eq!(owned[5], TOKEN::IDENTIFIER("x".into()));
+    assert_eq!(tokens[5], Token::Identifier("x"));
+}
+
+#[test]
+fn spans_cover_each_token_in_chars() {
+    let mut scanner = SCANNER::constructor("aé <= \"\\n\" /* c */ 0x1F".to_string());
+    let (tokens, _, spans) = scanner.tokenize_with_spans();
+    assert_eq!(tokens.len(), spans.len());
+    assert_eq!(spans, [(0, 2), (3, 5), (6, 10), (19, 23), (23, 23)]);
+}
+
+#[test]
+fn unicode_whitespace_is_skipped_up_to_eof() {
+    // char::is_whitespace counts the no-break space, so it separates tokens too
+    assert_eq!(tokenize("\u{00A0}1").unwrap(), [TOKEN::NUMBER("1".into()), TOKEN::EOF]);
+    assert_eq!(tokenize("1\u{00A0}+\u{2003}2").unwrap(), tokenize("1 + 2").unwrap());
+
+    // whitespace alone scans straight to EOF, which sits past all of it
+    let mut scanner = SCANNER::constructor(" \t\u{00A0}\n\u{3000} ".to_string());
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    assert_eq!(tokens, [TOKEN::EOF]);
+    assert_eq!((positions[0].line, positions[0].column), (2, 3));
+    assert_eq!(scanner.next(), None);
+}
+
+#[test]
+fn crlf_line_endings_scan_like_plain_newlines() {
+    let mut scanner = SCANNER::constructor("1+2\r\n3*4\r\n".to_string());
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    assert_eq!(tokens, tokenize("1+2\n3*4\n").unwrap());
+    assert!(!tokens.contains(&TOKEN::ERROR('\r')));
+    let at = |i: usize| (positions[i].line, positions[i].column);
+    assert_eq!((at(3), at(5)), ((2, 1), (2, 3)));
+    // EOF sits at the start of the empty third line; "\r\n" is one break
+    assert_eq!(at(6), (3, 1));
+    // a '\r' at the end of a // comment does not leak into the next line
+    assert_eq!(tokenize("1 // note\r\n+ 2").unwrap(), tokenize("1 + 2").unwrap());
+}
+
+#[test]
+fn double_star_is_pow_only_when_adjacent() {
+    let n = |text: &str| TOKEN::NUMBER(text.into());
+    assert_eq!(tokenize("2**3").unwrap(), [n("2"), TOKEN::POW, n("3"), TOKEN::EOF]);
+    assert_eq!(tokenize("2 * * 3").unwrap()[1..3], [TOKEN::STAR, TOKEN::STAR]);
+    assert_eq!(tokenize("2* *3").unwrap()[1..3], [TOKEN::STAR, TOKEN::STAR]);
+    // three stars take the pair first
+    assert_eq!(tokenize("2***3").unwrap()[1..3], [TOKEN::POW, TOKEN::STAR]);
+    assert_eq!(tokenize("2 /* a */** 3").unwrap()[1], TOKEN::POW);
+}
+
+#[test]
+fn disabled_operators_scan_as_errors() {
+    let no_star = ScannerConfig::default().star(false);
+    let scan = |input: &str, config| {
+        SCANNER::constructor(input.to_string()).with_config(config).tokenize_the_line()
+    };
+    let n = |text: &str| TOKEN::NUMBER(text.into());
+    assert_eq!(scan("2*3", no_star), [n("2"), TOKEN::ERROR('*'), n("3"), TOKEN::EOF]);
+    assert_eq!(scan("2+3", no_star), tokenize("2+3").unwrap());
+    // ** belongs to the power group, not to multiplication
+    assert_eq!(scan("2**3", no_star)[1], TOKEN::POW);
+    let no_power = ScannerConfig::default().power(false);
+    assert_eq!(scan("2**3", no_power), [n("2"), TOKEN::ERROR('*'), n("3"), TOKEN::EOF]);
+    // comments are not operators, so a disabled slash still allows them
+    let no_slash = ScannerConfig::default().slash(false);
+    assert_eq!(scan("1 // note", no_slash), [n("1"), TOKEN::EOF]);
+
+    // the parser rejects the error token like any other bad character
+    let mut scanner = SCANNER::constructor("2*3".to_string()).with_config(no_star);
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    let err = PARSER::constructor(tokens).with_positions(positions).parse().unwrap_err();
+    assert_eq!(err.position().map(|p| p.column), Some(2));
+}
+
+#[test]
+fn reset_scanner_starts_over_on_new_input() {
+    let mut scanner = SCANNER::constructor("1 +\n x".to_string()).at_line(7);
+    let (tokens, _) = scanner.tokenize_with_positions();
+    assert_eq!(tokens, tokenize("1 + x").unwrap());
+    assert_eq!(scanner.next(), None);
+
+    scanner.reset("foo(2)");
+    assert_eq!(scanner.current_position(), POSITION { line: 1, column: 1, offset: 0 });
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    assert_eq!(tokens, tokenize("foo(2)").unwrap());
+    assert_eq!((positions[2].line, positions[2].column), (1, 5));
+
+    // the operator config survives a reset
+    let mut scanner = SCANNER::constructor(String::new())
+        .with_config(ScannerConfig::default().plus(false));
+    scanner.reset("1+2");
+    assert_eq!(scanner.tokenize_the_line()[1], TOKEN::ERROR('+'));
+}
+
+#[test]
+fn reset_refills_the_same_buffer() {
+    let mut scanner = SCANNER::constructor("a much longer first line".to_string());
+    scanner.tokenize_the_line();
+    let buffer = scanner.source().as_ptr();
+    for line in ["1 + 2", "", "x * y"] {
+        scanner.reset(line);
+        assert_eq!(scanner.source(), line);
+        assert_eq!(scanner.source().as_ptr(), buffer, "{:?} got a new buffer", line);
+    }
+    assert_eq!(scanner.tokenize_the_line(), tokenize("x * y").unwrap());
+}
+
+#[test]
+fn checked_numbers_must_fit_at_scan_time() {
+    let big = "1234567890123456789012345678901234567890";
+    let err = tokenize_checked(&format!("1 + {}", big)).unwrap_err();
+    let at = POSITION { line: 1, column: 5, offset: 4 };
+    assert_eq!(err, ScanError::NumberOutOfRange { text: big.into(), at });
+    assert_eq!(err.to_string(), format!("number '{}' out of range", big));
+    // without the check the literal scans, and only eval would notice
+    assert_eq!(tokenize(big).unwrap()[0], TOKEN::NUMBER(big.into()));
+
+    let max = "9_223_372_036_854_775_807";
+    assert_eq!(tokenize_checked(max).unwrap()[0], TOKEN::NUMBER(max.into()));
+    assert!(tokenize_checked("0x8000000000000000").is_err());
+    assert!(tokenize_checked("1e400").is_err());
+    assert!(tokenize_checked("1.5e300").is_ok());
+
+    // the parser reports it where the literal starts
+    let mut scanner = SCANNER::constructor(format!("2 * {}", big)).check_numbers(true);
+    let (tokens, positions) = scanner.tokenize_with_positions();
+    assert_eq!(tokens[2], TOKEN::OVERFLOW(big.into()));
+    let err = PARSER::constructor(tokens).with_positions(positions).parse().unwrap_err();
+    assert_eq!(err.position().map(|p| p.column), Some(5));
+    assert_eq!(err.to_string(), format!("number '{}' out of range", big));
+}
+
+#[test]
+fn tricky_number_literals_scan_to_one_token_or_one_error() {
+    use TOKEN::{EOF, ERROR, IDENTIFIER, MINUS, NUMBER, PLUS};
+    let num = |text: &str| NUMBER(text.into());
+    let id = |name: &str| IDENTIFIER(name.into());
+    // a malformed literal is a single ERROR naming the char that broke it,
+    // never an error followed by the leftover pieces
+    let table: Vec<(&str, Vec<TOKEN>)> = vec![
+        (".", vec![ERROR('.'), EOF]),
+        (".5", vec![num(".5"), EOF]),
+        ("1.", vec![ERROR('.'), EOF]),
+        ("1.e3", vec![ERROR('.'), EOF]),
+        ("1.0e3", vec![num("1.0e3"), EOF]),
+        ("1..2", vec![ERROR('.'), EOF]),
+        ("1e-3", vec![num("1e-3"), EOF]),
+        ("1e3.5", vec![ERROR('.'), EOF]),
+        ("1_e3", vec![ERROR('_'), EOF]),
+        ("0x", vec![ERROR('x'), EOF]),
+        ("0xg", vec![ERROR('x'), EOF]),
+        ("0x1.8", vec![ERROR('.'), EOF]),
+        ("0x1.8p3", vec![ERROR('.'), EOF]),
+        ("0xF_F", vec![ERROR('_'), EOF]),
+        ("0b", vec![ERROR('b'), EOF]),
+        ("0b2", vec![ERROR('b'), EOF]),
+        // a letter or digit that cannot continue a well-formed literal just
+        // ends it and starts the next token
+        ("1x", vec![num("1"), id("x"), EOF]),
+        ("1e", vec![num("1"), id("e"), EOF]),
+        ("1ex", vec![num("1"), id("ex"), EOF]),
+        ("1e+", vec![num("1"), id("e"), PLUS, EOF]),
+        ("0xFG", vec![num("0xF"), id("G"), EOF]),
+        ("0b102", vec![num("0b10"), num("2"), EOF]),
+        // a sign is never part of the literal; unary minus is the parser's job
+        ("-1", vec![MINUS, num("1"), EOF]),
+        ("+1", vec![PLUS, num("1"), EOF]),
+        ("1e+5", vec![num("1e+5"), EOF]),
+    ];
+    for (input, expected) in table {
+        let tokens = SCANNER::constructor(input.to_string()).tokenize_the_line();
+        assert_eq!(tokens, expected, "{}", input);
+    }
+}
+
+#[test]
+fn histogram_counts_each_token_kind() {
+    let counts = token_histogram(&tokenize("1+2+3").unwrap());
+    assert_eq!(counts.len(), 2);
+    assert_eq!((counts["NUMBER"], counts["PLUS"]), (3, 2));
+    assert!(!counts.contains_key("EOF"));
+    assert_eq!(TOKEN::IDENTIFIER("x".into()).kind(), "IDENTIFIER");
+    assert_eq!(TOKEN::POW.kind(), "POW");
+}
+
+#[test]
+fn keep_whitespace_interleaves_the_exact_spacing() {
+    let scan = |input: &str| {
+        SCANNER::constructor(input.to_string()).keep_whitespace(true).tokenize_the_line()
+    };
+    let (n, ws) = (|t: &str| TOKEN::NUMBER(t.into()), |t: &str| TOKEN::WHITESPACE(t.into()));
+    assert_eq!(scan("1 +  2"), [n("1"), ws(" "), TOKEN::PLUS, ws("  "), n("2"), TOKEN::EOF]);
+    // each newline is its own token, and a '\r' before it stays in the run
+    let tokens = scan("\t1\r\n\n 2 // c\n");
+    assert_eq!(
+        tokens,
+        [
+            ws("\t"),
+            n("1"),
+            ws("\r"),
+            TOKEN::NEWLINE,
+            TOKEN::NEWLINE,
+            ws(" "),
+            n("2"),
+            ws(" "),
+            TOKEN::NEWLINE,
+            TOKEN::EOF,
+        ]
+    );
+    // printing the tokens gives back the input without its comments
+    let text: String = tokens[..tokens.len() - 1].iter().map(TOKEN::to_string).collect();
+    assert_eq!(text, "\t1\r\n\n 2 \n");
+    // the default scanner still skips it all
+    assert_eq!(tokenize("1 +  2").unwrap().len(), 4);
+}
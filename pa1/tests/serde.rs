@@ -0,0 +1,23 @@
+#![cfg(feature = "serde")]
+
+use pal::{NODE, SCANNER, TOKEN, parse_str, tokenize};
+
+#[test]
+fn tree_round_trips_through_serde_json() {
+    let tree = parse_str("f(x) + 2 ^ y * -z").unwrap();
+    let json = serde_json::to_string(&tree).unwrap();
+    let back: NODE = serde_json::from_str(&json).unwrap();
+    // PartialEq skips spans, so compare those separately
+    assert_eq!(back, tree);
+    assert_eq!(back.span, tree.span);
+    assert_eq!(back.children[0].span, tree.children[0].span);
+}
+
+#[test]
+fn tokens_round_trip_through_serde_json() {
+    let mut tokens = tokenize("a <= \"hi\" ** 0x1F").unwrap();
+    tokens.push(SCANNER::constructor("@".to_string()).tokenize_the_line()[0].clone());
+    assert_eq!(tokens.last(), Some(&TOKEN::ERROR('@')));
+    let json = serde_json::to_string(&tokens).unwrap();
+    assert_eq!(serde_json::from_str::<Vec<TOKEN>>(&json).unwrap(), tokens);
+}
@@ -0,0 +1,34 @@
+use pal::{NODE, Visitor, parse, tokenize, walk};
+
+// gathers every IDENTIFIER(...) label, in source order
+struct Identifiers(Vec<String>);
+
+impl Visitor for Identifiers {
+    fn visit(&mut self, node: &NODE) {
+        if node.label.starts_with("IDENTIFIER(") {
+            self.0.push(node.label.clone());
+        }
+    }
+}
+
+#[test]
+fn visitor_collects_identifiers() {
+    let tree = parse(tokenize("a + b * c").unwrap()).unwrap();
+    let mut identifiers = Identifiers(Vec::new());
+    walk(&tree, &mut identifiers);
+    assert_eq!(identifiers.0, ["IDENTIFIER(a)", "IDENTIFIER(b)", "IDENTIFIER(c)"]);
+}
+
+#[test]
+fn walk_is_pre_order() {
+    struct Labels(Vec<String>);
+    impl Visitor for Labels {
+        fn visit(&mut self, node: &NODE) {
+            self.0.push(node.label.clone());
+        }
+    }
+    let tree = NODE::with("A", vec![NODE::with("B", vec![NODE::leaf("C")]), NODE::leaf("D")]);
+    let mut labels = Labels(Vec::new());
+    walk(&tree, &mut labels);
+    assert_eq!(labels.0, ["A", "B", "C", "D"]);
+}